@@ -0,0 +1,232 @@
+use std::{io::Read, ops::Range};
+
+use serde::Serialize;
+
+use crate::rules::Finding;
+
+/// 解压单个对象流时的大小上限
+const MAX_OBJECT_STREAM_SIZE: u64 = 16 * 1024 * 1024;
+
+/// PDF 中可能在阅读时自动执行或携带其他文件的内容, 按出现次数计数
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub struct ActiveContent {
+    pub javascript: usize,
+    pub launch: usize,
+    pub embedded_files: usize,
+    pub auto_actions: usize,
+}
+
+impl ActiveContent {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    pub fn findings(&self) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        if self.javascript > 0 {
+            findings.push(Finding::new(
+                "pdf-javascript",
+                format!("PDF中含有JavaScript ({} 处)", self.javascript),
+            ));
+        }
+        if self.launch > 0 {
+            findings.push(Finding::new(
+                "pdf-launch-action",
+                format!("PDF中含有启动外部程序的动作 ({} 处)", self.launch),
+            ));
+        }
+        if self.embedded_files > 0 {
+            findings.push(Finding::new(
+                "pdf-embedded-file",
+                format!("PDF中含有嵌入的文件 ({} 处)", self.embedded_files),
+            ));
+        }
+        if self.auto_actions > 0 {
+            findings.push(Finding::new(
+                "pdf-auto-action",
+                format!(
+                    "PDF中含有打开文档或页面时自动执行的动作 ({} 处)",
+                    self.auto_actions
+                ),
+            ));
+        }
+        findings
+    }
+
+    fn count(&mut self, name: &[u8], next: Option<u8>) {
+        match name {
+            b"JavaScript" | b"JS" => self.javascript += 1,
+            b"Launch" => self.launch += 1,
+            b"EmbeddedFile" | b"EmbeddedFiles" => self.embedded_files += 1,
+            b"AA" => self.auto_actions += 1,
+            // OpenAction 的值为数组时只是指定打开后显示的页面
+            b"OpenAction" if next != Some(b'[') => self.auto_actions += 1,
+            _ => (),
+        }
+    }
+}
+
+fn is_delimiter(byte: u8) -> bool {
+    byte.is_ascii_whitespace() || b"()<>[]{}/%".contains(&byte) || byte == 0
+}
+
+/// 按 PDF 的词法找出所有名字对象, 并解码其中的 `#xx` 转义
+fn scan_names(bytes: &[u8], result: &mut ActiveContent) {
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] != b'/' {
+            index += 1;
+            continue;
+        }
+        let mut end = index + 1;
+        let mut name = Vec::new();
+        while end < bytes.len() && !is_delimiter(bytes[end]) {
+            if bytes[end] == b'#'
+                && let Some(hex) = bytes.get(end + 1..end + 3)
+                && let Ok(hex) = std::str::from_utf8(hex)
+                && let Ok(byte) = u8::from_str_radix(hex, 16)
+            {
+                name.push(byte);
+                end += 3;
+                continue;
+            }
+            name.push(bytes[end]);
+            end += 1;
+        }
+        let next = bytes[end..]
+            .iter()
+            .copied()
+            .find(|byte| !byte.is_ascii_whitespace());
+        result.count(&name, next);
+        index = end;
+    }
+}
+
+fn find(bytes: &[u8], pattern: &[u8], from: usize) -> Option<usize> {
+    bytes
+        .get(from..)?
+        .windows(pattern.len())
+        .position(|window| window == pattern)
+        .map(|position| position + from)
+}
+
+fn rfind(bytes: &[u8], pattern: &[u8]) -> Option<usize> {
+    bytes
+        .windows(pattern.len())
+        .rposition(|window| window == pattern)
+}
+
+/// 文件中的一个流: 关键字 `stream` 的位置和流数据的范围
+struct Stream {
+    keyword: usize,
+    data: Range<usize>,
+}
+
+/// 按 `stream` 和 `endstream` 关键字找出所有流, 没有 `endstream` 的流不计入
+fn find_streams(bytes: &[u8]) -> Vec<Stream> {
+    let mut streams = Vec::new();
+    let mut from = 0;
+    while let Some(start) = find(bytes, b"stream", from) {
+        from = start + 6;
+        if start >= 3 && &bytes[start - 3..start] == b"end" {
+            continue;
+        }
+        let data_start = match bytes.get(from..from + 2) {
+            Some(b"\r\n") => from + 2,
+            Some([b'\n', _]) | Some([b'\r', _]) => from + 1,
+            _ => continue,
+        };
+        let Some(data_end) = find(bytes, b"endstream", data_start) else {
+            break;
+        };
+        from = data_end + 9;
+        streams.push(Stream {
+            keyword: start,
+            data: data_start..data_end,
+        });
+    }
+    streams
+}
+
+/// 压缩的对象流 (ObjStm) 中的对象在文件中不可见, 需要解压后再扫描;
+/// 其他流的数据 (多为压缩的页面内容和图片) 不扫描, 以免随机字节被误认为名字
+fn scan_object_stream(bytes: &[u8], stream: &Stream, result: &mut ActiveContent) {
+    let dictionary_start = rfind(&bytes[..stream.keyword], b"obj").unwrap_or(0);
+    let dictionary = &bytes[dictionary_start..stream.keyword];
+    if find(dictionary, b"/ObjStm", 0).is_none() {
+        return;
+    }
+    let data = &bytes[stream.data.clone()];
+    if find(dictionary, b"/Filter", 0).is_none() {
+        scan_names(data, result);
+        return;
+    }
+    let mut decoded = Vec::new();
+    let decoder = flate2::read::ZlibDecoder::new(data);
+    if decoder
+        .take(MAX_OBJECT_STREAM_SIZE)
+        .read_to_end(&mut decoded)
+        .is_ok()
+    {
+        scan_names(&decoded, result);
+    }
+}
+
+/// 在不执行任何内容的前提下扫描 PDF 的原始字节, 统计可执行或自动触发的内容
+pub fn scan_active_content(bytes: &[u8]) -> ActiveContent {
+    let mut result = ActiveContent::default();
+    let mut from = 0;
+    for stream in find_streams(bytes) {
+        scan_names(&bytes[from..stream.data.start], &mut result);
+        scan_object_stream(bytes, &stream, &mut result);
+        from = stream.data.end;
+    }
+    scan_names(&bytes[from..], &mut result);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_scan_active_content() {
+        let clean = b"%PDF-1.7\n1 0 obj\n<< /Type /Catalog /Pages 2 0 R /OpenAction [3 0 R /Fit] >>\nendobj\n";
+        assert!(scan_active_content(clean).is_empty());
+
+        let hostile = b"1 0 obj\n<< /Type /Catalog /OpenAction 4 0 R /Names << /EmbeddedFiles 5 0 R >> >>\nendobj\n4 0 obj\n<< /S /J#61vaScript /JS (app.alert\\(1\\)) >>\nendobj\n6 0 obj<</S/Launch/F(cmd.exe)>>endobj";
+        assert_eq!(
+            scan_active_content(hostile),
+            ActiveContent {
+                javascript: 2,
+                launch: 1,
+                embedded_files: 1,
+                auto_actions: 1,
+            }
+        );
+
+        let mut encoder =
+            flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"7 0 << /AA << /O 8 0 R >> >>").unwrap();
+        let mut compressed =
+            b"5 0 obj\n<< /Type /ObjStm /N 1 /First 4 /Filter /FlateDecode >>\nstream\n".to_vec();
+        compressed.extend(encoder.finish().unwrap());
+        compressed.extend(b"\nendstream\nendobj\n");
+        let result = scan_active_content(&compressed);
+        assert_eq!(result.auto_actions, 1);
+        let rules = result
+            .findings()
+            .into_iter()
+            .map(|finding| finding.rule)
+            .collect::<Vec<_>>();
+        assert_eq!(rules, ["pdf-auto-action"]);
+
+        // 普通流中的数据不是名字, 即使恰好出现 /JS 或 /AA
+        let stream = b"3 0 obj\n<< /Length 20 /Filter /FlateDecode >>\nstream\n\x01/JS\x02/AA\xff/Launch\nendstream\nendobj\n4 0 obj\n<< /Type /Page >>\nendobj\n";
+        assert!(scan_active_content(stream).is_empty());
+        let mut uncompressed = b"5 0 obj\n<< /Type /ObjStm /N 1 /First 4 >>\nstream\n".to_vec();
+        uncompressed.extend(b"7 0 << /JS (x) >>\nendstream\nendobj\n");
+        assert_eq!(scan_active_content(&uncompressed).javascript, 1);
+    }
+}
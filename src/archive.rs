@@ -0,0 +1,309 @@
+use std::io::{Read, Seek};
+
+use zip::{HasZipMetadata, ZipArchive};
+
+use crate::rules::Finding;
+
+/// 解压后总大小的默认上限
+pub const DEFAULT_MAX_UNPACKED_SIZE: u64 = 4 * 1024 * 1024 * 1024;
+/// 单个文件压缩率的默认上限, 只对解压后超过 1MiB 的文件检查
+pub const DEFAULT_MAX_RATIO: u64 = 100;
+const RATIO_MIN_SIZE: u64 = 1024 * 1024;
+
+const ARCHIVE_EXTENSIONS: &[&str] = &["zip", "rar", "7z", "tar", "gz", "tgz", "bz2", "xz", "zst"];
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "dll", "com", "scr", "msi", "bat", "cmd", "ps1", "vbs", "lnk", "jar", "apk", "app", "sh",
+];
+
+pub struct ZipLimits {
+    pub max_unpacked_size: u64,
+    pub max_ratio: u64,
+}
+
+impl Default for ZipLimits {
+    fn default() -> Self {
+        Self {
+            max_unpacked_size: DEFAULT_MAX_UNPACKED_SIZE,
+            max_ratio: DEFAULT_MAX_RATIO,
+        }
+    }
+}
+
+/// 解码压缩包中的文件名: 没有 UTF-8 标记且不是有效 UTF-8 时按 GB18030 解码,
+/// 第二个返回值表示解码过程中是否有无法识别的字节
+pub fn decode_entry_name(raw: &[u8], is_utf8: bool) -> (String, bool) {
+    match std::str::from_utf8(raw) {
+        Ok(name) => (name.to_string(), false),
+        Err(_) if is_utf8 => (String::from_utf8_lossy(raw).into_owned(), true),
+        Err(_) => {
+            let (name, _, had_errors) = encoding_rs::GB18030.decode(raw);
+            (name.into_owned(), had_errors)
+        }
+    }
+}
+
+/// 把文件名拆成各级路径, 反斜杠也视为分隔符
+pub fn entry_parts(name: &str) -> Vec<&str> {
+    name.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect()
+}
+
+/// 预览和检查时忽略的文件: macOS 生成的 `__MACOSX`、隐藏文件和 Office 临时文件
+pub fn is_ignored(parts: &[&str]) -> bool {
+    parts.first() == Some(&"__MACOSX")
+        || parts
+            .iter()
+            .any(|part| part.starts_with('.') || part.starts_with("~$"))
+}
+
+fn is_traversal(name: &str) -> bool {
+    let bytes = name.as_bytes();
+    name.starts_with(['/', '\\'])
+        || (bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':')
+        || name.split(['/', '\\']).any(|part| part == "..")
+}
+
+fn extension(name: &str) -> Option<String> {
+    let (_, extension) = name.rsplit_once('.')?;
+    Some(extension.to_ascii_lowercase())
+}
+
+fn is_executable_magic(head: &[u8]) -> bool {
+    head.starts_with(b"MZ")
+        || head.starts_with(b"\x7fELF")
+        || head.starts_with(&[0xcf, 0xfa, 0xed, 0xfe])
+        || head.starts_with(&[0xca, 0xfe, 0xba, 0xbe])
+}
+
+/// 检查压缩包能否正常解压, 并找出不安全或不应出现的文件
+///
+/// 每个文件都会被实际解压一遍 (不写入磁盘), 这样既能发现损坏的文件,
+/// 也不会被压缩包中声明的错误大小欺骗
+pub fn check_zip<R: Read + Seek>(reader: R, limits: &ZipLimits) -> Vec<Finding> {
+    let mut zip = match ZipArchive::new(reader) {
+        Ok(zip) => zip,
+        Err(e) => {
+            return vec![Finding::new(
+                "zip-unreadable",
+                format!("无法打开压缩包: {}", e),
+            )];
+        }
+    };
+    let mut findings = Vec::new();
+    let mut unpacked_size = 0;
+    let mut files = 0;
+    for index in 0..zip.len() {
+        let (name, bad_encoding) = match zip.by_index_raw(index) {
+            Ok(entry) => decode_entry_name(entry.name_raw(), entry.get_metadata().is_utf8),
+            Err(e) => {
+                findings.push(Finding::new(
+                    "zip-unreadable",
+                    format!("无法读取压缩包中的第 {} 个文件: {}", index + 1, e),
+                ));
+                continue;
+            }
+        };
+        if bad_encoding {
+            findings.push(
+                Finding::new(
+                    "zip-filename-encoding",
+                    format!("文件名编码无法识别: {:?}", name),
+                )
+                .with_key(name.as_str()),
+            );
+        }
+        if is_traversal(&name) {
+            findings.push(
+                Finding::new(
+                    "zip-path-traversal",
+                    format!("文件路径指向压缩包之外: {:?}", name),
+                )
+                .with_key(name.as_str()),
+            );
+        }
+
+        let mut entry = match zip.by_index(index) {
+            Ok(entry) => entry,
+            Err(e) => {
+                findings.push(Finding::new(
+                    "zip-unreadable",
+                    format!("无法读取 {:?}: {}", name, e),
+                ));
+                continue;
+            }
+        };
+        if entry.is_dir() {
+            continue;
+        }
+        let compressed_size = entry.compressed_size();
+        let mut head = [0; 4];
+        let mut head_len = 0;
+        let mut size = 0;
+        let mut buffer = [0; 64 * 1024];
+        let limit = limits.max_unpacked_size - unpacked_size;
+        let result = loop {
+            match entry.read(&mut buffer) {
+                Ok(0) => break Ok(()),
+                Ok(read) => {
+                    if head_len < head.len() {
+                        let copied = read.min(head.len() - head_len);
+                        head[head_len..head_len + copied].copy_from_slice(&buffer[..copied]);
+                        head_len += copied;
+                    }
+                    size += read as u64;
+                    if size > limit {
+                        break Ok(());
+                    }
+                }
+                Err(e) => break Err(e),
+            }
+        };
+        drop(entry);
+        if let Err(e) = result {
+            findings.push(Finding::new(
+                "zip-unreadable",
+                format!("无法解压 {:?}: {}", name, e),
+            ));
+            continue;
+        }
+        unpacked_size += size;
+        if size > limit {
+            findings.push(Finding::new(
+                "zip-bomb",
+                format!(
+                    "解压后的总大小超过上限 {} 字节, 在 {:?} 处停止检查",
+                    limits.max_unpacked_size, name
+                ),
+            ));
+            return findings;
+        }
+        if size > RATIO_MIN_SIZE && size / compressed_size.max(1) > limits.max_ratio {
+            findings.push(Finding::new(
+                "zip-bomb",
+                format!(
+                    "{:?} 的压缩率异常: {} 字节压缩为 {} 字节",
+                    name, size, compressed_size
+                ),
+            ));
+        }
+
+        let parts = entry_parts(&name);
+        if is_ignored(&parts) {
+            continue;
+        }
+        files += 1;
+        let extension = extension(parts.last().copied().unwrap_or_default());
+        if extension
+            .as_deref()
+            .is_some_and(|extension| ARCHIVE_EXTENSIONS.contains(&extension))
+        {
+            findings.push(
+                Finding::new(
+                    "zip-nested-archive",
+                    format!("压缩包中含有其他压缩包: {:?}, 请解压后重新打包", name),
+                )
+                .with_key(name.as_str()),
+            );
+        }
+        if extension
+            .as_deref()
+            .is_some_and(|extension| EXECUTABLE_EXTENSIONS.contains(&extension))
+            || is_executable_magic(&head[..head_len])
+        {
+            findings.push(
+                Finding::new(
+                    "zip-executable",
+                    format!("压缩包中含有可执行文件: {:?}", name),
+                )
+                .with_key(name.as_str()),
+            );
+        }
+    }
+    if files == 0 {
+        findings.push(Finding::new(
+            "zip-empty",
+            "压缩包中除了 __MACOSX 和隐藏文件外没有其他文件",
+        ));
+    }
+    findings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Cursor, Write};
+    use zip::write::SimpleFileOptions;
+
+    fn build(files: &[(&str, &[u8])]) -> Cursor<Vec<u8>> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, content) in files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(content).unwrap();
+        }
+        let mut cursor = writer.finish().unwrap();
+        cursor.set_position(0);
+        cursor
+    }
+
+    fn rules(files: &[(&str, &[u8])], limits: &ZipLimits) -> Vec<&'static str> {
+        check_zip(build(files), limits)
+            .into_iter()
+            .map(|finding| finding.rule)
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_entry_name() {
+        assert_eq!(
+            decode_entry_name("数据结构.pdf".as_bytes(), false),
+            ("数据结构.pdf".to_string(), false)
+        );
+        let (gbk, _, _) = encoding_rs::GBK.encode("数据结构.pdf");
+        assert_eq!(
+            decode_entry_name(&gbk, false),
+            ("数据结构.pdf".to_string(), false)
+        );
+        assert!(decode_entry_name(&gbk, true).1);
+        assert_eq!(entry_parts("a\\b/./c/"), ["a", "b", "c"]);
+        assert!(is_ignored(&["__MACOSX", "a.pdf"]));
+        assert!(is_ignored(&["a", ".DS_Store"]));
+    }
+
+    #[test]
+    fn test_check_zip() {
+        let limits = ZipLimits::default();
+        assert!(rules(&[("a/原题.pdf", b"%PDF-1.4")], &limits).is_empty());
+        assert_eq!(
+            rules(&[("../a.pdf", b"x"), ("C:/b.pdf", b"x")], &limits),
+            ["zip-path-traversal", "zip-path-traversal"]
+        );
+        assert_eq!(
+            rules(&[("__MACOSX/._a", b"x"), (".DS_Store", b"x")], &limits),
+            ["zip-empty"]
+        );
+        assert_eq!(
+            rules(&[("a.rar", b"x"), ("setup", b"MZ\x90\x00")], &limits),
+            ["zip-nested-archive", "zip-executable"]
+        );
+        let zeros = vec![0; 4 * 1024 * 1024];
+        assert_eq!(rules(&[("a.txt", &zeros)], &limits), ["zip-bomb"]);
+        let small = ZipLimits {
+            max_unpacked_size: 16,
+            ..Default::default()
+        };
+        assert_eq!(
+            rules(
+                &[("a.txt", b"0123456789"), ("b.txt", b"0123456789")],
+                &small
+            ),
+            ["zip-bomb"]
+        );
+        assert_eq!(
+            check_zip(Cursor::new(b"not a zip".to_vec()), &limits)[0].rule,
+            "zip-unreadable"
+        );
+    }
+}
@@ -0,0 +1,48 @@
+/// `/api/file/notPublished` 的返回结果
+#[derive(serde::Deserialize, Debug)]
+#[allow(dead_code)]
+pub struct ApiResult {
+    pub success: bool,
+    pub files: Vec<TempFiles>,
+}
+
+/// 已经上传但还没有发布的文件
+#[derive(serde::Deserialize, Debug, Eq, PartialEq, Hash, Clone)]
+#[allow(dead_code)]
+pub struct TempFiles {
+    pub id: u64,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "fileName")]
+    pub file_name: String,
+    #[serde(rename = "fileSize")]
+    pub file_size: Option<u64>,
+    pub uploader: String,
+    #[serde(rename = "uploadTime")]
+    pub upload_time: Option<String>,
+    pub status: Status,
+    #[serde(rename = "errorMessage")]
+    pub error_message: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Eq, PartialEq, Hash, Clone)]
+pub enum Status {
+    Published,
+    Pending,
+    Timeout,
+    Expired,
+    Error,
+    Uploaded,
+}
+
+pub async fn get_temp_files(backend_url: &str, backend_token: &str) -> anyhow::Result<ApiResult> {
+    let backend_client = reqwest::Client::new();
+    let temp_files = backend_client
+        .get(format!("{}/api/file/notPublished", backend_url))
+        .bearer_auth(backend_token)
+        .send()
+        .await?
+        .json::<ApiResult>()
+        .await?;
+    Ok(temp_files)
+}
@@ -0,0 +1,179 @@
+use std::{collections::HashMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::rules::Diagnostic;
+
+/// 已知问题的基线, 在基线中的问题不会导致检查失败, 方便逐步启用新规则
+#[derive(Deserialize, Serialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Baseline {
+    pub findings: Vec<BaselineEntry>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct BaselineEntry {
+    pub file: String,
+    pub rule: String,
+    /// 规则给出的稳定标识, 如字段名或 isbn, 没有时同一文件中同一规则的问题按数量抵消
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    /// 只供阅读, 不参与匹配, 因为消息中可能含有行号、其他文件的 id 等会变化的内容
+    #[serde(default)]
+    pub message: String,
+}
+
+impl BaselineEntry {
+    fn match_key(&self) -> MatchKey {
+        (self.file.clone(), self.rule.clone(), self.key.clone())
+    }
+}
+
+type MatchKey = (String, String, Option<String>);
+
+impl Baseline {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read baseline {:?}: {}", path, e))?;
+        Ok(serde_json::from_str(&source)?)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// 记录所有未被豁免的问题, 无论其严重程度
+    pub fn from_diagnostics<'a>(
+        reports: impl IntoIterator<Item = (&'a str, &'a [Diagnostic])>,
+    ) -> Self {
+        let mut findings = Vec::new();
+        for (file, diagnostics) in reports {
+            for diagnostic in diagnostics.iter().filter(|d| !d.suppressed) {
+                findings.push(BaselineEntry {
+                    file: file.to_string(),
+                    rule: diagnostic.rule.to_string(),
+                    key: diagnostic.key.clone(),
+                    message: diagnostic.message.clone(),
+                });
+            }
+        }
+        findings.sort();
+        Self { findings }
+    }
+
+    pub fn matcher(&self) -> BaselineMatcher {
+        let mut remaining = HashMap::new();
+        for entry in &self.findings {
+            *remaining.entry(entry.match_key()).or_insert(0) += 1;
+        }
+        BaselineMatcher { remaining }
+    }
+}
+
+/// 每条基线记录只能抵消一个问题, 同一文件中新增的同类问题仍会被报告
+pub struct BaselineMatcher {
+    remaining: HashMap<MatchKey, usize>,
+}
+
+impl BaselineMatcher {
+    pub fn apply(&mut self, file: &str, diagnostics: &mut [Diagnostic]) {
+        for diagnostic in diagnostics.iter_mut().filter(|d| !d.suppressed) {
+            let key = (
+                file.to_string(),
+                diagnostic.rule.to_string(),
+                diagnostic.key.clone(),
+            );
+            if let Some(count) = self.remaining.get_mut(&key)
+                && *count > 0
+            {
+                *count -= 1;
+                diagnostic.baselined = true;
+            }
+        }
+    }
+
+    /// 基线中已经不再出现的问题数量
+    pub fn unused(&self) -> usize {
+        self.remaining.values().sum()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rules::Severity;
+
+    fn diagnostic(rule: &'static str, message: &str) -> Diagnostic {
+        Diagnostic {
+            rule,
+            message: message.to_string(),
+            key: None,
+            severity: Severity::Error,
+            suppressed: false,
+            baselined: false,
+        }
+    }
+
+    #[test]
+    fn test_baseline() {
+        let old = vec![diagnostic("time", "请检查时间")];
+        let baseline = Baseline::from_diagnostics([("a.yml", old.as_slice())]);
+        let baseline: Baseline =
+            serde_json::from_str(&serde_json::to_string(&baseline).unwrap()).unwrap();
+
+        let mut matcher = baseline.matcher();
+        let mut current = vec![
+            diagnostic("time", "请检查时间"),
+            diagnostic("time", "请检查时间"),
+            diagnostic("stage", "请检查stage"),
+        ];
+        matcher.apply("a.yml", &mut current);
+        assert!(!current[0].is_blocking());
+        assert!(current[1].is_blocking());
+        assert!(current[2].is_blocking());
+        assert_eq!(matcher.unused(), 0);
+
+        let mut matcher = baseline.matcher();
+        let mut other = vec![diagnostic("time", "请检查时间")];
+        matcher.apply("b.yml", &mut other);
+        assert!(other[0].is_blocking());
+        assert_eq!(matcher.unused(), 1);
+    }
+
+    #[test]
+    fn test_baseline_ignores_volatile_messages() {
+        let keyed = |message: &str, key: &str| Diagnostic {
+            key: Some(key.to_string()),
+            ..diagnostic("duplicate-value", message)
+        };
+        let old = vec![
+            keyed("authors中存在重复的值: a", "authors"),
+            diagnostic("duplicate-test", "与以下文件重复: x"),
+        ];
+        let baseline = Baseline::from_diagnostics([("a.yml", old.as_slice())]);
+
+        let mut matcher = baseline.matcher();
+        let mut current = vec![
+            keyed("authors中存在重复的值: b", "authors"),
+            keyed("content中存在重复的值: b", "content"),
+            diagnostic("duplicate-test", "与以下文件重复: x, y"),
+        ];
+        matcher.apply("a.yml", &mut current);
+        assert!(!current[0].is_blocking());
+        assert!(current[1].is_blocking());
+        assert!(!current[2].is_blocking());
+        assert_eq!(matcher.unused(), 0);
+
+        let old: Baseline = serde_json::from_str(
+            r#"{"findings": [{"file": "a.yml", "rule": "duplicate-value", "key": "authors"}]}"#,
+        )
+        .unwrap();
+        let mut current = vec![keyed("authors中存在重复的值: c", "authors")];
+        old.matcher().apply("a.yml", &mut current);
+        assert!(!current[0].is_blocking());
+    }
+}
@@ -0,0 +1,507 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use clap::{Args, Parser, Subcommand};
+
+use byrdocs_check::{
+    backend::get_temp_files,
+    citation::{to_bibtex, to_csl_json},
+    corpus::{Corpus, Layout},
+    coverage::{self, DEFAULT_RECENT_YEARS, find_gaps},
+    export::{write_csv, write_sqlite},
+    import::import_csv,
+    metadata::{MetaData, Type},
+    pdf::{bind_pdfium, extract_pages_text, read_document_info},
+    query::{Filter, to_table},
+    rules::{Config, Suppressions},
+    scaffold::{
+        DRAFT_HEADER, Fields, book_draft, detect_filetype, draft, missing_metadata, to_yaml,
+        upload_comment,
+    },
+    stats::{collect_stats, to_markdown},
+    storage::{fill_file_size, get_s3_client, list_all_objects},
+    validate::check_data,
+};
+
+/// 查找 ISBN 时读取的页数, 版权页一般在前几页
+const ISBN_PAGES: usize = 5;
+
+#[derive(Parser)]
+#[command(about = "BYR Docs 元信息工具")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 为本地的 PDF 或 ZIP 文件生成元信息, id、url 和 filetype 由文件内容得出
+    New {
+        /// PDF 或 ZIP 文件
+        file: PathBuf,
+        /// 元信息的类型: test、book 或 doc
+        #[arg(long = "type")]
+        type_: Type,
+        #[command(flatten)]
+        fields: Box<Fields>,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// 根据 PDF 的文档信息和前几页中的 ISBN 生成书籍元信息草稿
+    PrefillBook {
+        /// PDF 文件
+        pdf: PathBuf,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// 从 CSV 批量导入元信息, 每行一个文件, 有错误的行不会写入
+    Import {
+        /// CSV 文件, 本地路径相对于 CSV 所在的目录
+        csv: PathBuf,
+        /// 检查配置, 与 check-format 相同
+        #[arg(long, env = "CHECK_CONFIG")]
+        config: Option<PathBuf>,
+        /// 只检查, 不写入文件
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        output: Output,
+    },
+    /// 把所有元信息导出为 CSV 或 SQLite, 配置了 R2 时包含文件大小
+    #[command(group = clap::ArgGroup::new("format").required(true).multiple(true))]
+    Export {
+        /// 导出的 CSV 文件
+        #[arg(long, group = "format")]
+        csv: Option<PathBuf>,
+        /// 导出的 SQLite 数据库, 已经存在时会被替换
+        #[arg(long, group = "format")]
+        sqlite: Option<PathBuf>,
+        #[command(flatten)]
+        corpus: CorpusArgs,
+        #[command(flatten)]
+        r2: R2,
+    },
+    /// 输出所有书籍的 BibTeX 条目或 CSL-JSON
+    Cite {
+        #[arg(long, value_enum, default_value = "bibtex")]
+        format: CiteFormat,
+        #[command(flatten)]
+        corpus: CorpusArgs,
+    },
+    /// 按条件查询元信息, 例如某门课程缺少答案的试卷或缺少出版社的书籍
+    Query {
+        #[command(flatten)]
+        filter: Filter,
+        #[arg(long, value_enum, default_value = "table")]
+        format: QueryFormat,
+        #[command(flatten)]
+        corpus: CorpusArgs,
+    },
+    /// 按类型、课程、学院、学年、阶段和内容统计元信息, 配置了 R2 时包含文件大小
+    Stats {
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: ReportFormat,
+        #[command(flatten)]
+        corpus: CorpusArgs,
+        #[command(flatten)]
+        r2: R2,
+    },
+    /// 列出每门课程有原题没答案、有答案没原题的学年和阶段, 以及最近几个学年中没有试卷的考试
+    Coverage {
+        /// 检查最近几个学年是否有试卷
+        #[arg(long, default_value_t = DEFAULT_RECENT_YEARS)]
+        recent_years: i32,
+        /// 最近的学年 (time.start), 默认为所有试卷中最新的学年
+        #[arg(long)]
+        latest_year: Option<i32>,
+        #[arg(long, value_enum, default_value = "markdown")]
+        format: ReportFormat,
+        #[command(flatten)]
+        corpus: CorpusArgs,
+    },
+    /// 为已经上传但还没有元信息的文件生成草稿
+    Drafts {
+        /// 草稿使用的类型: test、book 或 doc, 整理时可以修改
+        #[arg(long = "type", default_value = "test")]
+        type_: Type,
+        /// 网站地址, 用于获取未发布的文件和生成文件链接
+        #[arg(long, env = "BYRDOCS_SITE_URL")]
+        site_url: String,
+        #[arg(long, env = "BYRDOCS_SITE_TOKEN", hide_env_values = true)]
+        site_token: String,
+        // 草稿写入元信息目录, 已经有元信息的文件会被跳过
+        #[command(flatten)]
+        corpus: CorpusArgs,
+    },
+}
+
+/// 读取的元信息目录
+#[derive(Args)]
+struct CorpusArgs {
+    /// 元信息目录
+    #[arg(long, env = "METADATA_DIR", default_value = ".")]
+    metadata_dir: PathBuf,
+    /// 元信息目录的布局
+    #[arg(long, env = "METADATA_LAYOUT", value_enum, default_value = "flat")]
+    layout: Layout,
+}
+
+impl CorpusArgs {
+    fn load(&self) -> anyhow::Result<Corpus> {
+        Corpus::load(&self.metadata_dir, self.layout)
+    }
+
+    /// 读取目录中所有元信息, 按 id 排序, 无法解析的文件会被跳过
+    fn load_metadata(&self) -> anyhow::Result<Vec<MetaData>> {
+        let corpus = self.load()?;
+        for (file, e) in corpus.errors() {
+            eprintln!("Skipping {:?}: {}", file.path, e);
+        }
+        let mut records = corpus.metadata().cloned().collect::<Vec<_>>();
+        records.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(records)
+    }
+}
+
+#[derive(Args)]
+struct Output {
+    /// 网站地址, 用于生成文件链接
+    #[arg(long, env = "BYRDOCS_SITE_URL")]
+    site_url: String,
+    /// 元信息的输出目录
+    #[arg(long, env = "METADATA_DIR", default_value = ".")]
+    output_dir: PathBuf,
+    /// 元信息目录的布局, 分片布局时写入对应的子目录
+    #[arg(long, env = "METADATA_LAYOUT", value_enum, default_value = "flat")]
+    layout: Layout,
+    /// 覆盖已经存在的元信息文件
+    #[arg(long)]
+    force: bool,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum CiteFormat {
+    Bibtex,
+    CslJson,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum QueryFormat {
+    /// 对齐的文本表格
+    Table,
+    /// 完整元信息的 JSON 数组
+    Json,
+    /// 每行一个 id
+    Ids,
+}
+
+#[derive(Clone, Copy, clap::ValueEnum)]
+enum ReportFormat {
+    Json,
+    Markdown,
+}
+
+/// 文件桶的配置, 全部提供时才会读取文件大小
+#[derive(Args)]
+struct R2 {
+    #[arg(long, env = "R2_ACCOUNT_ID")]
+    r2_account_id: Option<String>,
+    #[arg(long, env = "R2_ACCESS_KEY_ID")]
+    r2_access_key_id: Option<String>,
+    #[arg(long, env = "R2_SECRET_ACCESS_KEY", hide_env_values = true)]
+    r2_secret_access_key: Option<String>,
+    #[arg(long, env = "R2_FILE_BUCKET")]
+    r2_file_bucket: Option<String>,
+}
+
+/// 写入元信息文件, 分片布局时创建对应的子目录
+fn write_metadata(path: &Path, content: &str) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn write_draft(output: &Output, id: &str, yaml: &str) -> anyhow::Result<()> {
+    let path = output.layout.path(&output.output_dir, id);
+    if path.exists() && !output.force {
+        return Err(anyhow::anyhow!(
+            "{} already exists, use --force to overwrite",
+            path.display()
+        ));
+    }
+    write_metadata(&path, &format!("{}{}", DRAFT_HEADER, yaml))?;
+    println!("{}", path.display());
+    Ok(())
+}
+
+fn new(file: PathBuf, type_: Type, fields: Fields, output: Output) -> anyhow::Result<()> {
+    let bytes = std::fs::read(&file)?;
+    let filetype = detect_filetype(&bytes)
+        .ok_or_else(|| anyhow::anyhow!("{} is neither a PDF nor a ZIP file", file.display()))?;
+    let md5 = format!("{:x}", md5::compute(&bytes));
+    let metadata = draft(type_, &md5, filetype, &output.site_url, fields);
+    write_draft(&output, &md5, &to_yaml(&metadata)?)?;
+    for finding in check_data(&metadata.data) {
+        println!("  待补全: {}", finding.message);
+    }
+    Ok(())
+}
+
+fn prefill_book(pdf: PathBuf, output: Output) -> anyhow::Result<()> {
+    let bytes = std::fs::read(&pdf)?;
+    let md5 = format!("{:x}", md5::compute(&bytes));
+    let pdfium = bind_pdfium()?;
+    let document = pdfium.load_pdf_from_byte_slice(&bytes, None)?;
+    let info = read_document_info(&document);
+    let text = extract_pages_text(&document, ISBN_PAGES)?;
+    let draft = book_draft(&md5, &output.site_url, &info, &text);
+    write_draft(&output, &md5, &to_yaml(&draft)?)
+}
+
+fn import(
+    csv: PathBuf,
+    config: Option<PathBuf>,
+    dry_run: bool,
+    output: Output,
+) -> anyhow::Result<()> {
+    let config = match &config {
+        Some(path) => Config::load(path)?,
+        None => Config::default(),
+    };
+    let base_dir = csv.parent().unwrap_or(std::path::Path::new("."));
+    // 输出目录还不存在时没有需要比较的文件
+    let corpus = if output.output_dir.is_dir() {
+        Corpus::load(&output.output_dir, output.layout)?
+    } else {
+        Corpus::default()
+    };
+    let rows = import_csv(
+        std::fs::File::open(&csv)?,
+        base_dir,
+        &output.site_url,
+        &corpus,
+    );
+    let mut imported = 0;
+    let mut failed = 0;
+    for row in &rows {
+        let diagnostics = config.resolve(row.findings.clone(), &Suppressions::default());
+        let mut errors = Vec::new();
+        if let Some(metadata) = &row.metadata {
+            let path = output.layout.path(&output.output_dir, &metadata.id);
+            if path.exists() && !output.force {
+                errors.push(format!("{} 已经存在, 使用 --force 覆盖", path.display()));
+            }
+        }
+        if !diagnostics.is_empty() || !errors.is_empty() {
+            println!("第 {} 行:", row.line);
+            for diagnostic in &diagnostics {
+                println!("  {}", diagnostic);
+            }
+            for error in &errors {
+                println!("  {}", error);
+            }
+        }
+        match &row.metadata {
+            Some(metadata) if errors.is_empty() && !diagnostics.iter().any(|d| d.is_blocking()) => {
+                if !dry_run {
+                    let path = output.layout.path(&output.output_dir, &metadata.id);
+                    write_metadata(&path, &to_yaml(metadata)?)?;
+                }
+                imported += 1;
+            }
+            _ => failed += 1,
+        }
+    }
+    println!(
+        "Total: {}, Imported: {}, Failed: {}",
+        rows.len(),
+        imported,
+        failed
+    );
+    if failed != 0 {
+        Err(anyhow::anyhow!("{} rows failed to import", failed))
+    } else {
+        Ok(())
+    }
+}
+
+/// 配置了 R2 时从文件桶中读取文件大小, 与 metadata.json 相同
+async fn fill_file_sizes(records: &mut [MetaData], r2: R2) -> anyhow::Result<()> {
+    if let R2 {
+        r2_account_id: Some(account_id),
+        r2_access_key_id: Some(access_key_id),
+        r2_secret_access_key: Some(secret_access_key),
+        r2_file_bucket: Some(bucket),
+    } = r2
+    {
+        let s3_client = get_s3_client(
+            format!("https://{}.r2.cloudflarestorage.com", account_id),
+            "auto".to_owned(),
+            access_key_id,
+            secret_access_key,
+        )
+        .await?;
+        let s3_obj = list_all_objects(&s3_client, &bucket).await;
+        for metadata in records {
+            fill_file_size(metadata, &s3_obj);
+        }
+    } else {
+        eprintln!("R2 is not configured, filesize will be empty");
+    }
+    Ok(())
+}
+
+async fn export(
+    csv: Option<PathBuf>,
+    sqlite: Option<PathBuf>,
+    corpus: CorpusArgs,
+    r2: R2,
+) -> anyhow::Result<()> {
+    let mut records = corpus.load_metadata()?;
+    fill_file_sizes(&mut records, r2).await?;
+    if let Some(path) = csv {
+        write_csv(&records, std::fs::File::create(&path)?)?;
+        println!("CSV written to: {:?}", path);
+    }
+    if let Some(path) = sqlite {
+        if path.exists() {
+            std::fs::remove_file(&path)?;
+        }
+        write_sqlite(&records, &mut rusqlite::Connection::open(&path)?)?;
+        println!("SQLite database written to: {:?}", path);
+    }
+    println!("Exported {} records", records.len());
+    Ok(())
+}
+
+fn cite(format: CiteFormat, corpus: CorpusArgs) -> anyhow::Result<()> {
+    let records = corpus.load_metadata()?;
+    match format {
+        CiteFormat::Bibtex => print!("{}", to_bibtex(&records)),
+        CiteFormat::CslJson => {
+            println!("{}", serde_json::to_string_pretty(&to_csl_json(&records))?)
+        }
+    }
+    Ok(())
+}
+
+fn query(filter: Filter, format: QueryFormat, corpus: CorpusArgs) -> anyhow::Result<()> {
+    let records = corpus.load_metadata()?;
+    let matched = byrdocs_check::query::query(&records, &filter);
+    match format {
+        QueryFormat::Table => print!("{}", to_table(&matched)),
+        QueryFormat::Json => println!("{}", serde_json::to_string_pretty(&matched)?),
+        QueryFormat::Ids => {
+            for metadata in &matched {
+                println!("{}", metadata.id);
+            }
+        }
+    }
+    eprintln!("{} of {} records matched", matched.len(), records.len());
+    Ok(())
+}
+
+async fn stats(format: ReportFormat, corpus: CorpusArgs, r2: R2) -> anyhow::Result<()> {
+    let mut records = corpus.load_metadata()?;
+    fill_file_sizes(&mut records, r2).await?;
+    let stats = collect_stats(&records);
+    match format {
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&stats)?),
+        ReportFormat::Markdown => print!("{}", to_markdown(&stats)),
+    }
+    Ok(())
+}
+
+fn coverage(
+    recent_years: i32,
+    latest_year: Option<i32>,
+    format: ReportFormat,
+    corpus: CorpusArgs,
+) -> anyhow::Result<()> {
+    let records = corpus.load_metadata()?;
+    let coverage = find_gaps(&records, recent_years, latest_year);
+    match format {
+        ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&coverage)?),
+        ReportFormat::Markdown => print!("{}", coverage::to_markdown(&coverage)),
+    }
+    Ok(())
+}
+
+async fn drafts(
+    type_: Type,
+    site_url: String,
+    site_token: String,
+    corpus: CorpusArgs,
+) -> anyhow::Result<()> {
+    let existing = corpus
+        .load()?
+        .ids()
+        .map(str::to_string)
+        .collect::<HashSet<_>>();
+    let api_result = get_temp_files(&site_url, &site_token).await?;
+    let missing = missing_metadata(&api_result.files, &existing);
+    for (file, md5, filetype) in &missing {
+        let metadata = draft(type_, md5, filetype, &site_url, Fields::default());
+        let path = corpus.layout.path(&corpus.metadata_dir, md5);
+        write_metadata(
+            &path,
+            &format!(
+                "{}{}{}",
+                DRAFT_HEADER,
+                upload_comment(file),
+                to_yaml(&metadata)?
+            ),
+        )?;
+        println!("{}", path.display());
+    }
+    println!("Generated {} drafts", missing.len());
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Command::New {
+            file,
+            type_,
+            fields,
+            output,
+        } => new(file, type_, *fields, output),
+        Command::PrefillBook { pdf, output } => prefill_book(pdf, output),
+        Command::Import {
+            csv,
+            config,
+            dry_run,
+            output,
+        } => import(csv, config, dry_run, output),
+        Command::Export {
+            csv,
+            sqlite,
+            corpus,
+            r2,
+        } => export(csv, sqlite, corpus, r2).await,
+        Command::Cite { format, corpus } => cite(format, corpus),
+        Command::Query {
+            filter,
+            format,
+            corpus,
+        } => query(filter, format, corpus),
+        Command::Stats { format, corpus, r2 } => stats(format, corpus, r2).await,
+        Command::Coverage {
+            recent_years,
+            latest_year,
+            format,
+            corpus,
+        } => coverage(recent_years, latest_year, format, corpus),
+        Command::Drafts {
+            type_,
+            site_url,
+            site_token,
+            corpus,
+        } => drafts(type_, site_url, site_token, corpus).await,
+    }
+}
@@ -1,27 +1,55 @@
+use pdfium_render::prelude::{PdfDocument, Pdfium};
 use rusoto_core::HttpClient;
 use rusoto_s3::S3;
-use std::path::Path;
+use std::{collections::HashMap, path::Path};
 use tokio::{
     self,
     io::{AsyncReadExt, BufReader},
 };
 
-use byrdocs_check::{get_env, metadata::*};
+use byrdocs_check::{
+    active_content::scan_active_content,
+    archive::{self, ZipLimits, check_zip},
+    backend::{ApiResult, Status, get_temp_files},
+    baseline::Baseline,
+    corpus::{Corpus, Layout},
+    dedup::{check_duplicate_values, find_duplicate_tests, fix_duplicate_values},
+    file_index::FileIndex,
+    file_lint::{DEFAULT_MAX_FILE_SIZE, check_file},
+    get_env, get_optional_env,
+    hygiene::{check_text, fix_text},
+    malware::Scanner,
+    metadata::*,
+    pdf::{self, analyze_quality, bind_pdfium, open_pdf, render_cover},
+    phash,
+    rules::{Config, Diagnostic, Finding, Severity, Suppressions},
+    storage::list_all_objects,
+    validate::{check_data, check_duplicate_isbn},
+};
 
 struct Input {
     metadata_dir: String,
+    layout: Layout,
     r2_endpoint: String,
     r2_access_key_id: String,
     r2_secret_access_key: String,
     r2_file_bucket: String,
+    r2_data_bucket: Option<String>,
+    malware_scanner: Option<String>,
     byrdocs_site_url: String,
     byrdocs_site_token: String,
+    check_config: Option<String>,
+    check_baseline: Option<String>,
+    update_baseline: bool,
+    fix: bool,
+    skip_pdf: bool,
 }
 
 impl Input {
-    fn new() -> Self {
-        Input {
+    fn new() -> anyhow::Result<Self> {
+        Ok(Input {
             metadata_dir: get_env("METADATA_DIR"),
+            layout: Layout::from_env()?,
             r2_endpoint: format!(
                 "https://{}.r2.cloudflarestorage.com",
                 get_env("R2_ACCOUNT_ID"),
@@ -29,50 +57,59 @@ impl Input {
             r2_access_key_id: get_env("R2_ACCESS_KEY_ID"),
             r2_secret_access_key: get_env("R2_SECRET_ACCESS_KEY"),
             r2_file_bucket: get_env("R2_FILE_BUCKET"),
+            r2_data_bucket: get_optional_env("R2_DATA_BUCKET"),
+            malware_scanner: get_optional_env("MALWARE_SCANNER"),
             byrdocs_site_url: get_env("BYRDOCS_SITE_URL"),
             byrdocs_site_token: get_env("BYRDOCS_SITE_TOKEN"),
-        }
+            check_config: get_optional_env("CHECK_CONFIG"),
+            check_baseline: get_optional_env("CHECK_BASELINE"),
+            update_baseline: get_optional_env("CHECK_UPDATE_BASELINE")
+                .is_some_and(|value| value == "1" || value == "true"),
+            fix: get_optional_env("CHECK_FIX").is_some_and(|value| value == "1" || value == "true"),
+            skip_pdf: get_optional_env("CHECK_SKIP_PDF")
+                .is_some_and(|value| value == "1" || value == "true"),
+        })
     }
 }
 
-#[derive(serde::Deserialize, Debug)]
-#[allow(dead_code)]
-struct ApiResult {
-    success: bool,
-    files: Vec<TempFiles>,
+struct Context<'a> {
+    s3_file_list: &'a [String],
+    api_result: &'a ApiResult,
+    r2_file_bucket: &'a str,
+    s3_client: &'a rusoto_s3::S3Client,
+    byrdocs_site_url: &'a str,
+    file_index: Option<&'a FileIndex>,
+    pdfium: Option<&'a Pdfium>,
+    phash_distance: u32,
+    min_dpi: f32,
+    zip_limits: ZipLimits,
+    scanner: Option<&'a Scanner>,
 }
 
-#[derive(serde::Deserialize, Debug)]
-#[allow(dead_code)]
-struct TempFiles {
-    id: u64,
-    #[serde(rename = "createdAt")]
-    created_at: String,
-    #[serde(rename = "fileName")]
-    file_name: String,
-    #[serde(rename = "fileSize")]
-    file_size: Option<u64>,
-    uploader: String,
-    #[serde(rename = "uploadTime")]
-    upload_time: Option<String>,
-    status: Status,
-    #[serde(rename = "errorMessage")]
-    error_message: Option<String>,
+struct FileReport {
+    name: String,
+    type_: Option<Type>,
+    suppressions: Suppressions,
+    diagnostics: Vec<Diagnostic>,
+    notes: Vec<String>,
+    /// 新上传的 PDF 的页数
+    page_count: Option<usize>,
 }
 
-#[derive(serde::Deserialize, Debug)]
-enum Status {
-    Published,
-    Pending,
-    Timeout,
-    Expired,
-    Error,
-    Uploaded,
+impl FileReport {
+    fn is_blocking(&self) -> bool {
+        self.diagnostics.iter().any(Diagnostic::is_blocking)
+    }
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let input = Input::new();
+    let input = Input::new()?;
+
+    let config = match &input.check_config {
+        Some(path) => Config::load(Path::new(path))?,
+        None => Config::default(),
+    };
 
     let end_point = input.r2_endpoint;
     let http_client = HttpClient::new()?;
@@ -85,86 +122,276 @@ async fn main() -> anyhow::Result<()> {
         endpoint: end_point,
     };
     let s3_client = rusoto_s3::S3Client::new_with(http_client, credentials, region);
-    let s3_file_list = list_all_objects(&s3_client, &input.r2_file_bucket).await;
-
-    let backend_client = reqwest::Client::new();
-    let api_result = backend_client
-        .get(format!("{}/api/file/notPublished", input.byrdocs_site_url))
-        .bearer_auth(input.byrdocs_site_token)
-        .send()
-        .await
-        .unwrap()
-        .json::<ApiResult>()
+    let s3_file_list = list_all_objects(&s3_client, &input.r2_file_bucket)
         .await
-        .unwrap();
+        .into_iter()
+        .filter_map(|object| object.key)
+        .collect::<Vec<_>>();
+
+    let api_result = get_temp_files(&input.byrdocs_site_url, &input.byrdocs_site_token).await?;
+
+    let file_index = match &input.r2_data_bucket {
+        Some(bucket) => Some(FileIndex::download(&s3_client, bucket).await?),
+        None => None,
+    };
+    // 没有 pdfium 时大部分 PDF 检查无法进行, 只有明确要求时才跳过
+    let pdfium = if input.skip_pdf {
+        println!("已设置CHECK_SKIP_PDF, 跳过需要pdfium的PDF文件检查");
+        None
+    } else {
+        Some(bind_pdfium().map_err(|e| {
+            anyhow::anyhow!(
+                "Failed to load pdfium: {}. Set CHECK_SKIP_PDF=1 to skip PDF checks",
+                e
+            )
+        })?)
+    };
+
+    let scanner = input
+        .malware_scanner
+        .as_deref()
+        .map(Scanner::parse)
+        .transpose()?;
+
+    let context = Context {
+        s3_file_list: &s3_file_list,
+        api_result: &api_result,
+        r2_file_bucket: &input.r2_file_bucket,
+        s3_client: &s3_client,
+        byrdocs_site_url: &input.byrdocs_site_url,
+        file_index: file_index.as_ref(),
+        pdfium: pdfium.as_ref(),
+        phash_distance: config.phash_distance.unwrap_or(phash::DEFAULT_MAX_DISTANCE),
+        min_dpi: config.min_dpi.unwrap_or(pdf::DEFAULT_MIN_DPI),
+        zip_limits: ZipLimits {
+            max_unpacked_size: config
+                .zip_max_unpacked_size
+                .unwrap_or(archive::DEFAULT_MAX_UNPACKED_SIZE),
+            max_ratio: config.zip_max_ratio.unwrap_or(archive::DEFAULT_MAX_RATIO),
+        },
+        scanner: scanner.as_ref(),
+    };
 
-    let mut success_book = 0;
-    let mut success_test = 0;
-    let mut success_doc = 0;
     let mut total = 0;
+    let mut reports = Vec::new();
+    let mut isbns = HashMap::new();
+    let mut tests = Vec::new();
     let dir_path = Path::new(&input.metadata_dir);
+    let mut corpus = Corpus::load(dir_path, input.layout)?;
+    let mut fix_notes = HashMap::new();
+    if input.fix {
+        for file in &corpus.files {
+            let Some(source) = file.source() else {
+                continue;
+            };
+            let suppressions = Suppressions::parse(source);
+            let enabled =
+                |rule: &str| config.severity(rule) != Severity::Off && !suppressions.allows(rule);
+            let mut source = source.to_string();
+            let mut notes = Vec::new();
+            if let Some(fixed) = fix_text(&source, enabled) {
+                source = fixed;
+                notes.push("已自动修复文本格式".to_string());
+            }
+            if enabled("duplicate-value")
+                && let Some(fixed) = fix_duplicate_values(&source)
+            {
+                source = fixed;
+                notes.push("已自动去除重复的值".to_string());
+            }
+            if !notes.is_empty() {
+                std::fs::write(&file.path, &source)?;
+                fix_notes.insert(file.name.clone(), notes);
+            }
+        }
+        if !fix_notes.is_empty() {
+            corpus = Corpus::load(dir_path, input.layout)?;
+        }
+    }
 
-    for entry in dir_path.read_dir()? {
+    let mut entries = corpus
+        .other_files
+        .iter()
+        .map(|name| (name.as_str(), None))
+        .chain(
+            corpus
+                .files
+                .iter()
+                .map(|file| (file.name.as_str(), Some(file))),
+        )
+        .collect::<Vec<_>>();
+    entries.sort_by_key(|(name, _)| *name);
+
+    for (name, file) in entries {
         total += 1;
-        let entry = entry?;
-        let file_name = entry.file_name();
-        if !file_name.to_str().unwrap().ends_with(".yml") {
-            if file_name.to_str().unwrap().ends_with(".yaml") {
-                println!("{:?}:\n  请将.yaml改为.yml", file_name);
-            } else {
-                println!("{:?}:\n  请检查文件名后缀, 只能为\".yml\"", file_name);
+        let name = name.to_string();
+        let mut findings = Vec::new();
+        let mut suppressions = Suppressions::default();
+        let mut type_ = None;
+        let mut notes = fix_notes.remove(&name).unwrap_or_default();
+        let mut page_count = None;
+
+        match file {
+            None if name.ends_with(".yaml") => {
+                findings.push(Finding::new("file-extension", "请将.yaml改为.yml"));
             }
-            continue;
-        }
-        let path = entry.path();
-        if path.is_file() {
-            let file = std::fs::File::open(&path)?;
-            let metadata: MetaData = match serde_yaml::from_reader(file) {
-                Ok(metadata) => metadata,
-                Err(e) => {
-                    println!("{:?}:\n  格式错误: {:?}", path.file_name().unwrap(), e);
-                    continue;
+            None => {
+                findings.push(Finding::new(
+                    "file-extension",
+                    "请检查文件名后缀, 只能为\".yml\"",
+                ));
+            }
+            Some(file) => {
+                findings.extend(check_file(
+                    &file.bytes,
+                    config.max_file_size.unwrap_or(DEFAULT_MAX_FILE_SIZE),
+                ));
+                findings.extend(input.layout.check_location(file));
+                findings.extend(corpus.check_duplicate_id(file));
+                if let Some(source) = file.source() {
+                    suppressions = Suppressions::parse(source);
+                    findings.append(&mut suppressions.findings);
+                    match &file.metadata {
+                        Ok(metadata) => {
+                            findings.extend(check_data(&metadata.data));
+                            findings.extend(check_text(&metadata.data));
+                            findings.extend(check_duplicate_values(&metadata.data));
+                            if let Data::Book(book) = &metadata.data {
+                                findings.extend(check_duplicate_isbn(
+                                    book,
+                                    &metadata.id,
+                                    &mut isbns,
+                                ));
+                            }
+                            findings.extend(
+                                check(metadata, &file.path, &context, &mut notes, &mut page_count)
+                                    .await,
+                            );
+                            type_ = Some(metadata.type_);
+                            if let Data::Test(test) = &metadata.data {
+                                tests.push((reports.len(), &metadata.id, test));
+                            }
+                        }
+                        Err(e) => {
+                            findings
+                                .push(Finding::new("parse-error", format!("格式错误: {:?}", e)));
+                        }
+                    }
                 }
-            };
+            }
+        }
 
-            if let Err(e) = check(
-                &metadata,
-                &metadata.id,
-                &s3_file_list,
-                &path,
-                &api_result,
-                &input.r2_file_bucket,
-                &s3_client,
-                &input.byrdocs_site_url,
-            )
-            .await
-            {
-                println!("{:?}:\n  {:?}", path.file_name().unwrap(), e);
-                continue;
-            };
+        reports.push(FileReport {
+            name,
+            type_,
+            diagnostics: config.resolve(findings, &suppressions),
+            suppressions,
+            notes,
+            page_count,
+        });
+    }
+
+    // 以 (id, 报告序号) 为键, 多个文件使用同一个 id 时也能找到各自的报告
+    let groups = find_duplicate_tests(
+        tests
+            .iter()
+            .map(|(index, id, test)| ((id.as_str(), *index), *test)),
+    );
+    for group in groups {
+        let ids = group.iter().map(|(id, _)| *id).collect::<Vec<_>>();
+        for &(_, index) in &group {
+            let report = &mut reports[index];
+            let finding = Finding::new(
+                "duplicate-test",
+                format!("可能与其他试卷重复: {}", ids.join(", ")),
+            );
+            let diagnostics = config.resolve(vec![finding], &report.suppressions);
+            report.diagnostics.extend(diagnostics);
+        }
+    }
 
-            match metadata.data {
-                Data::Test(_) => {
-                    success_test += 1;
-                }
-                Data::Book(_) => {
-                    success_book += 1;
-                }
-                Data::Doc(_) => {
-                    success_doc += 1;
-                }
+    let mut unused_baseline = 0;
+    if let Some(path) = &input.check_baseline {
+        let path = Path::new(path);
+        let baseline = if input.update_baseline {
+            let baseline = Baseline::from_diagnostics(
+                reports
+                    .iter()
+                    .map(|report| (report.name.as_str(), report.diagnostics.as_slice())),
+            );
+            baseline.save(path)?;
+            println!(
+                "Baseline written to {:?}: {} findings",
+                path,
+                baseline.findings.len()
+            );
+            baseline
+        } else {
+            Baseline::load(path)?
+        };
+        let mut matcher = baseline.matcher();
+        for report in &mut reports {
+            matcher.apply(&report.name, &mut report.diagnostics);
+        }
+        unused_baseline = matcher.unused();
+    }
+
+    let mut success_book = 0;
+    let mut success_test = 0;
+    let mut success_doc = 0;
+    let mut failed = 0;
+    let mut warnings = 0;
+    for report in &reports {
+        if report.is_blocking() {
+            failed += 1;
+        } else {
+            match report.type_ {
+                Some(Type::Test) => success_test += 1,
+                Some(Type::Book) => success_book += 1,
+                Some(Type::Doc) => success_doc += 1,
+                None => (),
+            }
+        }
+        warnings += report
+            .diagnostics
+            .iter()
+            .filter(|diagnostic| diagnostic.severity == Severity::Warning && diagnostic.is_active())
+            .count();
+        if !report.diagnostics.is_empty() || !report.notes.is_empty() {
+            println!("{:?}:", report.name);
+            for diagnostic in &report.diagnostics {
+                println!("  {}", diagnostic);
+            }
+            for note in &report.notes {
+                println!("  {}", note);
             }
         }
     }
+    // 页数集中在一行输出, 不让每个 PDF 都出现在上面的问题列表中
+    let page_counts = reports
+        .iter()
+        .filter_map(|report| Some(format!("{} {} 页", report.name, report.page_count?)))
+        .collect::<Vec<_>>();
+    if !page_counts.is_empty() {
+        println!("新上传的PDF: {}", page_counts.join(", "));
+    }
+
     println!(
-        "Total: {}, Success: {}, Book: {}, Test: {}, Doc: {}",
+        "Total: {}, Success: {}, Book: {}, Test: {}, Doc: {}, Warning: {}",
         total,
         success_book + success_test + success_doc,
         success_book,
         success_test,
-        success_doc
+        success_doc,
+        warnings
     );
-    if total != success_book + success_test + success_doc {
+    if unused_baseline != 0 {
+        println!(
+            "基线中有 {} 个问题已被修复，可以重新生成基线",
+            unused_baseline
+        );
+    }
+    if failed != 0 {
         return Err(anyhow::anyhow!(
             "自动检查不通过，请按照上述报错信息修改您的元信息"
         ));
@@ -174,306 +401,161 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn list_all_objects(client: &rusoto_s3::S3Client, bucket_name: &str) -> Vec<String> {
-    let mut continuation_token: Option<String> = None;
-    let mut s3_file_list = Vec::new();
-
-    loop {
-        let request = rusoto_s3::ListObjectsV2Request {
-            bucket: bucket_name.to_string(),
-            continuation_token: continuation_token.clone(),
-            ..Default::default()
-        };
-
-        match client.list_objects_v2(request).await {
-            Ok(output) => {
-                if let Some(contents) = output.contents {
-                    s3_file_list.extend(
-                        contents
-                            .iter()
-                            .map(|item| item.key.clone().unwrap())
-                            .collect::<Vec<_>>(),
-                    )
-                }
-
-                if let Some(next_continuation_token) = output.next_continuation_token {
-                    continuation_token = Some(next_continuation_token);
-                } else {
-                    break;
-                }
-            }
-            Err(rusoto_core::RusotoError::Unknown(resp)) => {
-                println!("Error: {}", resp.status);
-                break;
-            }
-            Err(e) => {
-                println!("Error: {:?}", e);
-                break;
-            }
-        }
-    }
-    s3_file_list
-}
-
-use std::sync::Mutex;
-use std::sync::OnceLock;
-static ISBNS: OnceLock<Mutex<Vec<(isbn::Isbn13, String)>>> = OnceLock::new();
-
 async fn check(
     metadata: &MetaData,
-    md5: &str,
-    s3_file_list: &Vec<String>,
     path: &Path,
-    api_result: &ApiResult,
-    r2_file_bucket: &str,
-    s3_client: &rusoto_s3::S3Client,
-    byrdocs_site_url: &str,
-) -> anyhow::Result<()> {
-    let mut errors = Vec::new();
-
-    let data = &metadata.data;
-    if let Err(e) = match data {
-        Data::Test(test) => check_test(test),
-        Data::Book(book) => check_book(book, md5),
-        Data::Doc(doc) => check_doc(doc),
-    } {
-        errors.push(e);
-    }
+    context: &Context<'_>,
+    notes: &mut Vec<String>,
+    page_count: &mut Option<usize>,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
 
     let url_regex = regex::Regex::new(&format!(
         r"^{}/files/[a-fA-F0-9]{{32}}\.(pdf|zip)$",
-        regex::escape(&byrdocs_site_url),
+        regex::escape(context.byrdocs_site_url),
     ))
     .unwrap();
 
-    if !s3_file_list.contains(&metadata.url[metadata.url.len() - 36..].to_string()) {
-        errors.push(anyhow::anyhow!("请检查文件是否上传"));
+    if !context
+        .s3_file_list
+        .contains(&metadata.url[metadata.url.len() - 36..].to_string())
+    {
+        findings.push(Finding::new("file-uploaded", "请检查文件是否上传"));
     }
 
     if !url_regex.is_match(&metadata.url) {
-        errors.push(anyhow::anyhow!("请检查url是否填写正确"));
+        findings.push(Finding::new("url-format", "请检查url是否填写正确"));
     }
 
     if !metadata.url.contains(metadata.id.as_str()) {
-        errors.push(anyhow::anyhow!("请检查url中文件名与id是否匹配"));
+        findings.push(Finding::new(
+            "url-id-mismatch",
+            "请检查url中文件名与id是否匹配",
+        ));
     }
 
-    if !(path.file_name().unwrap().to_str().unwrap() == format!("{}.yml", metadata.id)) {
-        errors.push(anyhow::anyhow!("请检查文件名是否与id匹配"));
+    if path.file_name().unwrap().to_str().unwrap() != format!("{}.yml", metadata.id) {
+        findings.push(Finding::new(
+            "filename-id-mismatch",
+            "请检查文件名是否与id匹配",
+        ));
     }
 
-    let mut unmatched = false;
-    for temp_file in &api_result.files {
+    for temp_file in &context.api_result.files {
         if temp_file.file_name.as_str() == format!("{}.pdf", metadata.id)
             || temp_file.file_name.as_str() == format!("{}.zip", metadata.id)
         {
             match temp_file.status {
                 Status::Expired => {
-                    errors.push(anyhow::anyhow!("文件已过期，请重新上传"));
+                    findings.push(Finding::new("upload-status", "文件已过期，请重新上传"));
                 }
                 Status::Timeout | Status::Pending => {
-                    errors.push(anyhow::anyhow!("文件未上传成功，请重新上传"));
+                    findings.push(Finding::new("upload-status", "文件未上传成功，请重新上传"));
                 }
                 Status::Error => {
-                    errors.push(anyhow::anyhow!("api 未知错误"));
+                    findings.push(Finding::new("upload-status", "api 未知错误"));
                 }
-                _ => {
-                    let request = rusoto_s3::GetObjectRequest {
-                        bucket: r2_file_bucket.to_string(),
-                        key: metadata.url[metadata.url.len() - 36..].to_string(),
-                        ..Default::default()
-                    };
-                    let file = s3_client.get_object(request).await?.body.unwrap();
-                    let mut reader = BufReader::new(file.into_async_read());
-                    let mut context = md5::Context::new();
-                    let mut buffer = [0; 8192];
-                    loop {
-                        let bytes_read = reader.read(&mut buffer).await?;
-                        if bytes_read == 0 {
-                            break;
+                _ => match download_file(context, &metadata.url[metadata.url.len() - 36..]).await {
+                    Ok(bytes) => {
+                        if format!("{:x}", md5::compute(&bytes)) != metadata.id {
+                            findings.push(Finding::new("md5-mismatch", "md5不匹配"));
+                        } else {
+                            if let Some(scanner) = context.scanner {
+                                findings
+                                    .extend(tokio::task::block_in_place(|| scanner.check(&bytes)));
+                            }
+                            if metadata.url.ends_with(".pdf") {
+                                findings.extend(check_pdf(
+                                    &metadata.id,
+                                    &bytes,
+                                    context,
+                                    notes,
+                                    page_count,
+                                ));
+                            } else if metadata.url.ends_with(".zip") {
+                                findings.extend(check_zip(
+                                    std::io::Cursor::new(&bytes),
+                                    &context.zip_limits,
+                                ));
+                            }
                         }
-                        context.consume(&buffer[..bytes_read]);
                     }
-                    let md5 = context.compute();
-                    if !(format!("{:x}", md5) == metadata.id) {
-                        unmatched = true;
+                    Err(e) => {
+                        findings.push(Finding::new(
+                            "file-uploaded",
+                            format!("下载文件失败: {}", e),
+                        ));
                     }
-                }
+                },
             }
             break;
         }
     }
-    if unmatched {
-        errors.push(anyhow::anyhow!("md5不匹配"));
-    }
-
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        let error_messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
-        Err(anyhow::anyhow!(error_messages.join("\n")))
-    }
-}
-
-fn check_book(book: &Book, md5: &str) -> anyhow::Result<()> {
-    let mut errors = Vec::new();
-    if book.authors.len() == 0 {
-        errors.push(anyhow::anyhow!("应当至少有一个作者"));
-    }
-    let year_regex = regex::Regex::new(r"^\d{4}$").unwrap();
-    if let Some(year) = &book.publish_year {
-        if !year_regex.is_match(year) {
-            errors.push(anyhow::anyhow!("请检查出版年份"));
-        }
-    }
-    book.isbn.iter().for_each(|isbn| {
-        if let Err(e) = isbn.parse::<isbn::Isbn13>() {
-            errors.push(anyhow::anyhow!("请检查isbn格式: {}", e));
-        }
-    });
-    for isbn in book.isbn.clone() {
-        let isbn = isbn.parse::<isbn::Isbn13>().unwrap();
 
-        let mut isbns_lock = ISBNS.get_or_init(|| Mutex::new(Vec::new())).lock().unwrap();
-        if let Some((_, existing_md5)) = isbns_lock.iter().find(|(i, _)| i == &isbn) {
-            errors.push(anyhow::anyhow!("重复的isbn. md5: {} {}", md5, existing_md5));
-        } else {
-            isbns_lock.push((isbn, md5.to_string()));
-        }
-    }
-    if book.filetype != "pdf" {
-        errors.push(anyhow::anyhow!("请检查filetype，只能为pdf"));
-    }
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        let error_messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
-        Err(anyhow::anyhow!(error_messages.join("\n")))
-    }
+    findings
 }
 
-fn check_test(test: &Test) -> anyhow::Result<()> {
-    let mut errors = Vec::new();
-    if let Some(test) = &test.course.type_ {
-        if !["本科", "研究生"].contains(&test.as_str()) {
-            errors.push(anyhow::anyhow!(
-                "请检查course type，只能为\"本科\"或\"研究生\""
-            ));
-        }
-    }
-    if let Some(stage) = &test.time.stage {
-        if !["期中", "期末"].contains(&stage.as_str()) {
-            errors.push(anyhow::anyhow!("请检查stage，只能为\"期中\"或\"期末\""));
-        }
-    }
-    if let Some(semester) = &test.time.semester {
-        if !["First", "Second"].contains(&semester.as_str()) {
-            errors.push(anyhow::anyhow!(
-                "请检查semester，只能为\"First\"或\"Second\""
-            ));
-        }
-    }
-    for content in &test.content {
-        match content.as_str() {
-            "原题" => (),
-            "答案" => (),
-            _ => errors.push(anyhow::anyhow!(
-                "错误的content，content只能为\"原题\"或\"答案\""
-            )),
-        }
-    }
-    if let Some(colleges) = &test.college {
-        if colleges.contains(&"".to_string()) {
-            errors.push(anyhow::anyhow!("college不能存在空字符串"));
-        }
-    }
-    if let (Ok(start), Ok(end)) = (test.time.start.parse::<u32>(), test.time.end.parse::<u32>()) {
-        if !(start == end || start + 1 == end) {
-            errors.push(anyhow::anyhow!("请检查时间"));
-        }
-    } else {
-        errors.push(anyhow::anyhow!("时间格式不正确"));
-    }
-    if test.filetype != "pdf" && test.filetype != "zip" {
-        errors.push(anyhow::anyhow!("请检查filetype，只能为pdf或zip"));
-    }
-    if test.content.len() == 0 {
-        errors.push(anyhow::anyhow!("content不能为空"));
-    }
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        let error_messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
-        Err(anyhow::anyhow!(error_messages.join("\n")))
-    }
+async fn download_file(context: &Context<'_>, key: &str) -> anyhow::Result<Vec<u8>> {
+    let request = rusoto_s3::GetObjectRequest {
+        bucket: context.r2_file_bucket.to_string(),
+        key: key.to_string(),
+        ..Default::default()
+    };
+    let file = context.s3_client.get_object(request).await?.body.unwrap();
+    let mut reader = BufReader::new(file.into_async_read());
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).await?;
+    Ok(buf)
 }
 
-fn check_doc(doc: &Doc) -> anyhow::Result<()> {
-    let mut errors = Vec::new();
-    if &doc.course.len() == &0 {
-        errors.push(anyhow::anyhow!("course不能为空"));
-    }
-    for course in &doc.course {
-        if let Some(test) = &course.type_ {
-            if !["本科", "研究生"].contains(&test.as_str()) {
-                errors.push(anyhow::anyhow!(
-                    "请检查course type，只能为\"本科\"或\"研究生\""
-                ));
-            }
-        }
-    }
-    if &doc.content.len() == &0 {
-        errors.push(anyhow::anyhow!("content不能为空"));
-    }
-    for content in &doc.content {
-        match content.as_str() {
-            "思维导图" => (),
-            "题库" => (),
-            "答案" => (),
-            "知识点" => (),
-            "课件" => (),
-            _ => errors.push(anyhow::anyhow!(
-                r#"错误的content，content只能为"思维导图"、"题库"、"答案"、"知识点"或"课件""#
-            )),
+/// 检查新上传的 PDF 是否含有可执行内容、能否正常打开, 记录页数, 并与已发布的文件比较
+fn check_pdf(
+    id: &str,
+    bytes: &[u8],
+    context: &Context<'_>,
+    notes: &mut Vec<String>,
+    page_count: &mut Option<usize>,
+) -> Vec<Finding> {
+    let mut findings = scan_active_content(bytes).findings();
+    let Some(pdfium) = context.pdfium else {
+        return findings;
+    };
+    let document = match open_pdf(pdfium, bytes) {
+        Ok(document) => document,
+        Err(finding) => {
+            findings.push(finding);
+            return findings;
         }
+    };
+    *page_count = Some(document.pages().len() as usize);
+    match analyze_quality(&document) {
+        Ok(quality) => findings.extend(quality.findings(context.min_dpi)),
+        Err(e) => notes.push(format!("无法分析扫描质量: {}", e)),
     }
-    if doc.filetype != "pdf" && doc.filetype != "zip" {
-        errors.push(anyhow::anyhow!("请检查filetype，只能为pdf或zip"));
-    }
-    if errors.is_empty() {
-        Ok(())
-    } else {
-        let error_messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
-        Err(anyhow::anyhow!(error_messages.join("\n")))
-    }
+    findings.extend(check_similar(id, &document, context));
+    findings
 }
 
-#[cfg(test)]
-mod test {
-    #[test]
-    fn test_isbn() {
-        let isbn = "978-7-111-40772-1";
-        assert_eq!(
-            isbn.parse::<isbn::Isbn13>(),
-            Err(isbn::IsbnError::InvalidDigit)
-        );
-        let isbn = "978-7-111-40772-0";
-        assert_eq!(
-            isbn.parse::<isbn::Isbn13>(),
-            Ok(isbn::Isbn13::new([9, 7, 8, 7, 1, 1, 1, 4, 0, 7, 7, 2, 0]).unwrap())
-        );
-        let isbn = "9787111407720";
-        assert_eq!(
-            isbn.parse::<isbn::Isbn13>(),
-            Ok(isbn::Isbn13::new([9, 7, 8, 7, 1, 1, 1, 4, 0, 7, 7, 2, 0]).unwrap())
-        );
-        assert_eq!(
-            "978-7-111-40772-0",
-            isbn.parse::<isbn::Isbn13>()
-                .unwrap()
-                .hyphenate()
-                .unwrap()
-                .as_str()
-        );
-    }
+/// 用第一页的感知哈希与已发布的文件比较, 找出同一份资料的重复扫描
+fn check_similar(id: &str, document: &PdfDocument, context: &Context<'_>) -> Vec<Finding> {
+    let Some(file_index) = context.file_index else {
+        return Vec::new();
+    };
+    let hash = match render_cover(document) {
+        Ok(image) => phash::dhash(&image),
+        Err(_) => return Vec::new(),
+    };
+    file_index
+        .find_similar(id, hash, context.phash_distance)
+        .into_iter()
+        .map(|(other, distance)| {
+            Finding::new(
+                "similar-file",
+                format!(
+                    "第一页与已有文件 {} 相近 (距离 {}), 请确认是否重复上传",
+                    other, distance
+                ),
+            )
+        })
+        .collect()
 }
@@ -1,11 +1,10 @@
 use chrono::{DateTime, FixedOffset, NaiveDate};
-use pdfium_render::prelude::{PdfRenderConfig, Pdfium};
-use rusoto_core::HttpClient;
+use pdfium_render::prelude::Pdfium;
 use rusoto_s3::{Object, S3, S3Client};
 use serde::Serialize;
 use serde_json::{json, to_string_pretty};
 use std::{
-    collections::{HashMap, HashSet},
+    collections::{BTreeMap, HashMap, HashSet},
     path::{Path, PathBuf},
     process::Command,
 };
@@ -17,14 +16,34 @@ use tokio::{
 use webp::Encoder;
 use zip::{HasZipMetadata, ZipArchive};
 
-use byrdocs_check::{get_env, get_env_or, get_optional_env, metadata::*};
+use byrdocs_check::{
+    active_content::scan_active_content,
+    archive::{decode_entry_name, entry_parts, is_ignored},
+    backend::{ApiResult, TempFiles, get_temp_files},
+    citation::{BIBTEX_NAME, CSL_JSON_NAME, to_bibtex, to_csl_json},
+    corpus::{Corpus, CorpusFile, Layout},
+    file_index::{FILE_INDEX_NAME, FileIndex},
+    fulltext::{FULLTEXT_INDEX_NAME, FullTextIndex},
+    get_env, get_env_or, get_optional_env,
+    malware::Scanner,
+    metadata::*,
+    pdf::{bind_pdfium, extract_outline, extract_text, has_text_layer, render_cover},
+    phash,
+    rules::{Finding, Severity, find_rule},
+    stats::{STATS_JSON_NAME, STATS_MARKDOWN_NAME, collect_stats, to_markdown},
+    storage::{download_data_json, fill_file_size, get_s3_client, list_all_objects},
+};
 
+/// 书签文件的本地目录, 上传到数据桶的 outlines/ 下
+const OUTLINE_DIR: &str = "./outlines";
 const SITEMAP_MIN_LASTMOD_ENV: &str = "SITEMAP_MIN_LASTMOD";
 const GITHUB_API_USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
 const BYRDOCS_SITE_REPO_OWNER_ENV: &str = "BYRDOCS_SITE_REPO_OWNER";
 const BYRDOCS_SITE_REPO_NAME_ENV: &str = "BYRDOCS_SITE_REPO_NAME";
 const DEFAULT_BYRDOCS_SITE_REPO_OWNER: &str = "byrdocs";
 const DEFAULT_BYRDOCS_SITE_REPO_NAME: &str = "byrdocs";
+/// 每次运行最多为多少个之前发布的 PDF 补建全文索引
+const DEFAULT_FULLTEXT_BACKFILL: usize = 20;
 
 struct SitemapMinLastmod {
     raw: String,
@@ -46,43 +65,9 @@ struct GitHubCommitAuthor {
     date: String,
 }
 
-#[derive(serde::Deserialize, Debug)]
-#[allow(dead_code)]
-struct ApiResult {
-    success: bool,
-    files: Vec<TempFiles>,
-}
-
-#[derive(serde::Deserialize, Debug, Eq, PartialEq, Hash, Clone)]
-#[allow(dead_code)]
-struct TempFiles {
-    id: u64,
-    #[serde(rename = "createdAt")]
-    created_at: String,
-    #[serde(rename = "fileName")]
-    file_name: String,
-    #[serde(rename = "fileSize")]
-    file_size: Option<u64>,
-    uploader: String,
-    #[serde(rename = "uploadTime")]
-    upload_time: Option<String>,
-    status: Status,
-    #[serde(rename = "errorMessage")]
-    error_message: Option<String>,
-}
-
-#[derive(serde::Deserialize, Debug, Eq, PartialEq, Hash, Clone)]
-enum Status {
-    Published,
-    Pending,
-    Timeout,
-    Expired,
-    Error,
-    Uploaded,
-}
-
 struct Input {
     metadata_dir: String,
+    layout: Layout,
     r2_endpoint: String,
     r2_access_key_id: String,
     r2_secret_access_key: String,
@@ -96,12 +81,17 @@ struct Input {
     backup_secret_access_key: String,
     backup_file_bucket: String,
     backup_endpoint_region: String,
+    run_report: String,
+    block_flagged_files: bool,
+    malware_scanner: Option<String>,
+    fulltext_backfill: usize,
 }
 
 impl Input {
-    fn new() -> Self {
-        Self {
+    fn new() -> anyhow::Result<Self> {
+        Ok(Self {
             metadata_dir: get_absolute_path_from_env("METADATA_DIR"),
+            layout: Layout::from_env()?,
             r2_endpoint: format!(
                 "https://{}.r2.cloudflarestorage.com",
                 get_env("R2_ACCOUNT_ID"),
@@ -118,7 +108,17 @@ impl Input {
             backup_secret_access_key: get_env("BACKUP_SECRET_ACCESS_KEY"),
             backup_file_bucket: get_env("BACKUP_FILE_BUCKET"),
             backup_endpoint_region: get_env_or("BACKUP_ENDPOINT_REGION","auto".to_string()),
-        }
+            run_report: get_env_or("RUN_REPORT", "./run-report.json".to_string()),
+            block_flagged_files: get_optional_env("BLOCK_FLAGGED_FILES")
+                .is_some_and(|value| value == "1" || value == "true"),
+            malware_scanner: get_optional_env("MALWARE_SCANNER"),
+            fulltext_backfill: match get_optional_env("FULLTEXT_BACKFILL") {
+                Some(value) => value
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid FULLTEXT_BACKFILL {:?}: {}", value, e))?,
+                None => DEFAULT_FULLTEXT_BACKFILL,
+            },
+        })
     }
 }
 
@@ -139,7 +139,7 @@ fn get_absolute_path_from_env(key: &str) -> String {
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let input = Input::new();
+    let input = Input::new()?;
 
     if !Path::new(&input.metadata_dir).exists() {
         eprintln!(
@@ -166,7 +166,8 @@ async fn main() -> anyhow::Result<()> {
     .await?;
     let mut api_result = get_temp_files(&input.byrdocs_site_url, &input.byrdocs_site_token).await?;
 
-    let need_publish_files = get_publish_files(&input.metadata_dir, &mut api_result).await?; // get publish list and remove published files from temp_files
+    let corpus = Corpus::load(Path::new(&input.metadata_dir), input.layout)?;
+    let mut need_publish_files = get_publish_files(&corpus, &mut api_result).await?; // get publish list and remove published files from temp_files
 
     //image part
 
@@ -186,16 +187,49 @@ async fn main() -> anyhow::Result<()> {
     )
     .await?;
 
-    generate_pdf_covers(nocover_files.clone()).await?;
+    let scanner = input
+        .malware_scanner
+        .as_deref()
+        .map(Scanner::parse)
+        .transpose()?;
+    let mut run_report = RunReport::default();
+    // 扫描使用阻塞的读写, 不占用异步运行时的工作线程
+    tokio::task::block_in_place(|| {
+        scan_publish_files(&need_publish_files, scanner.as_ref(), &mut run_report)
+    })?;
+    let mut blocked = HashSet::new();
+    need_publish_files.retain(|file| {
+        if !run_report.block(&file.file_name, input.block_flagged_files) {
+            return true;
+        }
+        blocked.insert(file.file_name[..32].to_string());
+        false
+    });
+    remove_blocked_files(&blocked)?;
+    let nocover_files = &nocover_files - &blocked;
+
+    let mut file_index = FileIndex::download(&s3_client, &input.r2_data_bucket).await?;
+    generate_pdf_covers(nocover_files.clone(), &mut file_index).await?;
+    file_index.save(Path::new(FILE_INDEX_NAME))?;
 
     generate_zip_preview(&input.filelist_site_url, nocover_files.clone()).await?;
 
     reduce_webp_size().await?;
 
-    upload_images(&s3_client, input.r2_file_bucket).await?;
+    upload_images(&s3_client, input.r2_file_bucket.clone()).await?;
 
     //image part over
 
+    let mut fulltext_index: FullTextIndex =
+        download_data_json(&s3_client, &input.r2_data_bucket, FULLTEXT_INDEX_NAME).await?;
+    let pdfium = bind_pdfium()?;
+    update_fulltext_index(&pdfium, &corpus, &need_publish_files, &mut fulltext_index)?;
+    extract_book_outlines(&pdfium, &corpus, &need_publish_files)?;
+    let just_published = need_publish_files
+        .iter()
+        .map(|file| file.file_name[..32].to_string())
+        .collect::<HashSet<_>>();
+
     publish_files(
         need_publish_files,
         input.byrdocs_site_url.clone(),
@@ -203,11 +237,51 @@ async fn main() -> anyhow::Result<()> {
     )
     .await?; // publish files
 
+    // 在发布之后补建全文索引, 旧文件的问题不会影响本次发布
+    let previously_published = published_files(&corpus, &blocked)
+        .map(CorpusFile::id)
+        .filter(|md5| !just_published.contains(*md5))
+        .collect::<HashSet<_>>();
+    let backfill_keys = fulltext_backfill_keys(
+        &s3_obj,
+        &previously_published,
+        &fulltext_index,
+        &file_index,
+        input.fulltext_backfill,
+    );
+    backfill_fulltext_index(
+        &pdfium,
+        &s3_client,
+        &input.r2_file_bucket,
+        backfill_keys,
+        &mut fulltext_index,
+        &mut file_index,
+    )
+    .await;
+    file_index.save(Path::new(FILE_INDEX_NAME))?;
+    std::fs::write(FULLTEXT_INDEX_NAME, serde_json::to_string(&fulltext_index)?)?;
+
     // Upload to backup storage
     backup_files(&backup_client, input.backup_file_bucket).await?;
 
-    merge_json(&input.metadata_dir, &s3_obj).await?;
-    generate_sitemap(&input.metadata_dir, &input.byrdocs_site_url).await?;
+    run_report.save(Path::new(&input.run_report))?;
+
+    let records = merge_json(&corpus, &blocked, &input.metadata_dir, &s3_obj, &file_index).await?;
+    std::fs::write(BIBTEX_NAME, to_bibtex(&records))?;
+    std::fs::write(
+        CSL_JSON_NAME,
+        serde_json::to_string(&to_csl_json(&records))?,
+    )?;
+    let stats = collect_stats(&records);
+    std::fs::write(STATS_JSON_NAME, serde_json::to_string(&stats)?)?;
+    std::fs::write(STATS_MARKDOWN_NAME, to_markdown(&stats))?;
+    generate_sitemap(
+        &corpus,
+        &blocked,
+        &input.metadata_dir,
+        &input.byrdocs_site_url,
+    )
+    .await?;
 
     upload_metadata(
         input.r2_endpoint,
@@ -223,23 +297,6 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn get_s3_client(
-    r2_endpoint: String,
-    r2_endpoint_region: String,
-    access_key_id: String,
-    secret_access_key: String,
-) -> anyhow::Result<S3Client> {
-    let http_client = HttpClient::new()?;
-    let credentials =
-        rusoto_core::credential::StaticProvider::new_minimal(access_key_id, secret_access_key);
-    let region = rusoto_core::Region::Custom {
-        name: r2_endpoint_region,
-        endpoint: r2_endpoint,
-    };
-    let s3_client = rusoto_s3::S3Client::new_with(http_client, credentials, region);
-    Ok(s3_client)
-}
-
 async fn get_nocover_files(
     s3_obj: &Vec<Object>,
     temp_files: &ApiResult,
@@ -315,13 +372,13 @@ async fn download_files(
     Ok(())
 }
 
-async fn generate_pdf_covers(nocover_files: HashSet<String>) -> anyhow::Result<()> {
+async fn generate_pdf_covers(
+    nocover_files: HashSet<String>,
+    file_index: &mut FileIndex,
+) -> anyhow::Result<()> {
     println!("Generating images");
     let dir = Path::new("./tmp1");
-    let pdfium = Pdfium::new(
-        Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./lib"))
-            .or_else(|_| Pdfium::bind_to_system_library())?,
-    );
+    let pdfium = bind_pdfium()?;
     let mut error_count = 0;
     for file in dir.read_dir()? {
         let file = file?;
@@ -342,22 +399,19 @@ async fn generate_pdf_covers(nocover_files: HashSet<String>) -> anyhow::Result<(
                 context.consume(&buffer[..bytes_read]);
             }
             let md5 = context.compute();
-            if !(format!("{:x}", md5) == path.file_stem().unwrap().to_str().unwrap()) {
+            if format!("{:x}", md5) != path.file_stem().unwrap().to_str().unwrap() {
                 println!("MD5 mismatch: {:x}", md5);
                 error_count += 1;
                 continue;
             } // Additional check for md5 mismatch
             match pdfium.load_pdf_from_file(path.to_str().unwrap(), None) {
                 Ok(document) => {
-                    let render_config = PdfRenderConfig::new()
-                        .set_target_width(2000)
-                        .set_maximum_height(2000);
-                    let document_image = document
-                        .pages()
-                        .get(0)
-                        .unwrap()
-                        .render_with_config(&render_config)?
-                        .as_image();
+                    let document_image = render_cover(&document)?;
+                    let info = file_index.entry(&format!("{:x}", md5));
+                    info.phash = Some(phash::to_hex(phash::dhash(&document_image)));
+                    // 只需要页数和是否有文字, 不做逐页渲染的质量分析
+                    info.page_count = Some(document.pages().len() as usize);
+                    info.has_text_layer = Some(has_text_layer(&document));
                     document_image.to_rgb8().save_with_format(
                         format!(
                             "./tmp2/{}.jpg",
@@ -397,6 +451,114 @@ async fn generate_pdf_covers(nocover_files: HashSet<String>) -> anyhow::Result<(
     Ok(())
 }
 
+/// 本次运行中对待发布文件的检查结果, 以文件名为键
+#[derive(Serialize, Default)]
+struct RunReport {
+    files: BTreeMap<String, FileRunReport>,
+}
+
+#[derive(Serialize, Default)]
+struct FileRunReport {
+    findings: Vec<Finding>,
+    blocked: bool,
+}
+
+impl RunReport {
+    fn add(&mut self, file_name: &str, findings: Vec<Finding>) {
+        if findings.is_empty() {
+            return;
+        }
+        for finding in &findings {
+            println!(
+                "{}: {}[{}]: {}",
+                file_name,
+                find_rule(finding.rule).unwrap().default,
+                finding.rule,
+                finding.message
+            );
+        }
+        self.files
+            .entry(file_name.to_string())
+            .or_default()
+            .findings
+            .extend(findings);
+    }
+
+    /// 病毒扫描未通过的文件总是阻止发布, `block_flagged` 时含有默认为错误的问题也阻止发布
+    fn block(&mut self, file_name: &str, block_flagged: bool) -> bool {
+        let Some(report) = self.files.get_mut(file_name) else {
+            return false;
+        };
+        report.blocked = report.findings.iter().any(|finding| {
+            matches!(finding.rule, "malware" | "malware-scan-failed")
+                || (block_flagged && find_rule(finding.rule).unwrap().default == Severity::Error)
+        });
+        if report.blocked {
+            println!("Blocked from publishing: {}", file_name);
+        }
+        report.blocked
+    }
+
+    fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, to_string_pretty(self)?)?;
+        println!(
+            "Run report written to {:?}: {} flagged files",
+            path,
+            self.files.len()
+        );
+        Ok(())
+    }
+}
+
+/// 删除 ./tmp1 中被阻止发布的文件, 以免生成封面和上传到备份桶
+fn remove_blocked_files(blocked: &HashSet<String>) -> anyhow::Result<()> {
+    for file in Path::new("./tmp1").read_dir()? {
+        let path = file?.path();
+        let name = path.file_name().unwrap().to_string_lossy();
+        if name.get(..32).is_some_and(|md5| blocked.contains(md5)) {
+            std::fs::remove_file(&path)?;
+            println!("Removed blocked file: {:?}", path);
+        }
+    }
+    Ok(())
+}
+
+/// 生成 metadata.json、站点地图等产物时使用的元信息文件, 不含本次被阻止发布的文件
+fn published_files<'a>(
+    corpus: &'a Corpus,
+    blocked: &'a HashSet<String>,
+) -> impl Iterator<Item = &'a CorpusFile> {
+    corpus
+        .files
+        .iter()
+        .filter(|file| !blocked.contains(file.id()))
+}
+
+/// 扫描已下载到 ./tmp1 的待发布文件
+fn scan_publish_files(
+    files: &HashSet<TempFiles>,
+    scanner: Option<&Scanner>,
+    report: &mut RunReport,
+) -> anyhow::Result<()> {
+    println!("Scanning files");
+    for file in files {
+        let path = Path::new("./tmp1").join(&file.file_name);
+        if !path.is_file() {
+            continue;
+        }
+        let bytes = std::fs::read(&path)?;
+        let mut findings = Vec::new();
+        if let Some(scanner) = scanner {
+            findings.extend(scanner.check(&bytes));
+        }
+        if file.file_name.ends_with(".pdf") {
+            findings.extend(scan_active_content(&bytes).findings());
+        }
+        report.add(&file.file_name, findings);
+    }
+    Ok(())
+}
+
 #[derive(Debug, Serialize)]
 struct FileNode {
     #[serde(rename = "type")]
@@ -415,28 +577,9 @@ fn build_tree(zip_path: &str) -> Result<Vec<FileNode>, anyhow::Error> {
         let entry = zip.by_index(i)?;
         let is_dir = entry.is_dir();
 
-        let raw_name = entry.name_raw();
-
-        let name = if entry.get_metadata().is_utf8 || String::from_utf8(raw_name.to_vec()).is_ok() {
-            String::from_utf8_lossy(raw_name).into_owned()
-        } else {
-            let (cow, _, _) = encoding_rs::GB18030.decode(raw_name);
-            cow.into_owned()
-        };
-
-        let path = name.trim_matches('/').to_string();
-
-        let parts: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
-        if parts.is_empty() {
-            continue;
-        }
-        if parts[0] == "__MACOSX" {
-            continue;
-        }
-        if parts
-            .iter()
-            .any(|part| part.starts_with('.') || part.starts_with("~$"))
-        {
+        let (name, _) = decode_entry_name(entry.name_raw(), entry.get_metadata().is_utf8);
+        let parts = entry_parts(&name);
+        if parts.is_empty() || is_ignored(&parts) {
             continue;
         }
 
@@ -816,37 +959,11 @@ async fn backup_files(s3_client: &S3Client, bucket: String) -> anyhow::Result<()
     }
 }
 
-async fn get_temp_files(backend_url: &str, backend_token: &str) -> anyhow::Result<ApiResult> {
-    let backend_client = reqwest::Client::new();
-    let temp_files = backend_client
-        .get(format!("{}/api/file/notPublished", backend_url))
-        .bearer_auth(backend_token)
-        .send()
-        .await?
-        .json::<ApiResult>()
-        .await?;
-    Ok(temp_files)
-}
-
 async fn get_publish_files(
-    dir: &str,
+    corpus: &Corpus,
     api_result: &mut ApiResult,
 ) -> anyhow::Result<HashSet<TempFiles>> {
-    let path = Path::new(dir);
-    let mut local_files = HashSet::new();
-    for file in path.read_dir()? {
-        let file = file?;
-        let path = file.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("yml") {
-            let file_name = path
-                .file_name()
-                .unwrap()
-                .to_string_lossy()
-                .as_ref()
-                .to_string();
-            local_files.insert(file_name[..32].to_string());
-        }
-    }
+    let local_files = corpus.ids().map(str::to_string).collect::<HashSet<_>>();
 
     let temp_filename = api_result
         .files
@@ -890,7 +1007,7 @@ async fn publish_files(
         .await
         .unwrap();
 
-    if true == res["success"].as_bool().unwrap() {
+    if res["success"].as_bool().unwrap() {
         println!("{} Files published", ids.len());
         Ok(())
     } else {
@@ -902,44 +1019,34 @@ async fn publish_files(
     } //Publish files
 }
 
-async fn merge_json(dir: &str, s3_obj: &[Object]) -> anyhow::Result<()> {
+async fn merge_json(
+    corpus: &Corpus,
+    blocked: &HashSet<String>,
+    dir: &str,
+    s3_obj: &[Object],
+    file_index: &FileIndex,
+) -> anyhow::Result<Vec<MetaData>> {
     let dir = Path::new(dir);
+    corpus.ensure_parsed()?;
     let mut json = Vec::new();
-    for metadata in dir.read_dir()? {
-        let metadata = metadata?;
-        let path = metadata.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("yml") {
-            let file = File::open(&path).await?;
-            let mut reader = BufReader::new(file);
-            let mut buf = Vec::new();
-            reader.read_to_end(&mut buf).await?;
-            let mut metadata: MetaData = serde_yaml::from_slice(&buf)?;
-
-            match metadata.data {
-                Data::Book(ref mut book) => {
-                    book.filesize = get_file_size(&metadata.id, s3_obj);
-                    book.isbn = book
-                        .isbn
-                        .iter()
-                        .map(|isbn| {
-                            isbn.parse::<isbn::Isbn13>()
-                                .unwrap()
-                                .hyphenate()
-                                .unwrap()
-                                .to_string()
-                        })
-                        .collect();
-                }
-                Data::Doc(ref mut doc) => {
-                    doc.filesize = get_file_size(&metadata.id, s3_obj);
-                }
-                Data::Test(ref mut test) => {
-                    test.filesize = get_file_size(&metadata.id, s3_obj);
-                }
-            }
-
-            json.push(metadata);
+    for metadata in published_files(corpus, blocked).filter_map(|file| file.metadata.as_ref().ok())
+    {
+        let mut metadata = metadata.clone();
+        fill_file_size(&mut metadata, s3_obj);
+        if let Data::Book(ref mut book) = metadata.data {
+            book.isbn = book.isbn.iter().map(|isbn| hyphenate_isbn(isbn)).collect();
+        }
+        if let Some(info) = file_index.files.get(&metadata.id) {
+            let (page_count, has_text_layer) = match metadata.data {
+                Data::Book(ref mut book) => (&mut book.page_count, &mut book.has_text_layer),
+                Data::Doc(ref mut doc) => (&mut doc.page_count, &mut doc.has_text_layer),
+                Data::Test(ref mut test) => (&mut test.page_count, &mut test.has_text_layer),
+            };
+            *page_count = info.page_count;
+            *has_text_layer = info.has_text_layer;
         }
+
+        json.push(metadata);
     }
     let temp_file_path = dir.join("metadata.json");
     let mut temp_file = File::create(&temp_file_path).await?;
@@ -947,10 +1054,15 @@ async fn merge_json(dir: &str, s3_obj: &[Object]) -> anyhow::Result<()> {
     temp_file.write_all(json_data.as_bytes()).await?;
     println!("Metadata JSON written to: {:?}", temp_file_path);
     //merge to json
-    Ok(())
+    Ok(json)
 }
 
-async fn generate_sitemap(dir: &str, site_url: &str) -> anyhow::Result<()> {
+async fn generate_sitemap(
+    corpus: &Corpus,
+    blocked: &HashSet<String>,
+    dir: &str,
+    site_url: &str,
+) -> anyhow::Result<()> {
     let dir = Path::new(dir);
     let site_url = site_url.trim_end_matches('/');
     let metadata_git_dir = get_git_root(dir).unwrap_or_else(|_| dir.to_path_buf());
@@ -964,31 +1076,20 @@ async fn generate_sitemap(dir: &str, site_url: &str) -> anyhow::Result<()> {
     let about_lastmod = homepage_lastmod.clone();
 
     let mut md5_entries = Vec::new();
-    for metadata in dir.read_dir()? {
-        let metadata = metadata?;
-        let path = metadata.path();
-        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("yml") {
-            let md5 = path
-                .file_stem()
-                .and_then(|s| s.to_str())
-                .ok_or_else(|| anyhow::anyhow!("Invalid metadata filename: {:?}", path))?;
-            let file_name = path
-                .file_name()
-                .and_then(|s| s.to_str())
-                .ok_or_else(|| anyhow::anyhow!("Invalid metadata filename: {:?}", path))?;
-            let rel_path = path
-                .strip_prefix(&metadata_git_dir)
-                .unwrap_or(path.as_path())
-                .to_string_lossy()
-                .replace('\\', "/");
-            let lastmod = metadata_lastmods
-                .get(&rel_path)
-                .cloned()
-                .or_else(|| metadata_lastmods.get(file_name).cloned())
-                .ok_or_else(|| anyhow::anyhow!("Missing git lastmod for {:?}", path))?;
-            let lastmod = clamp_sitemap_lastmod(&lastmod, sitemap_min_lastmod.as_ref())?;
-            md5_entries.push((md5.to_string(), lastmod));
-        }
+    for file in published_files(corpus, blocked) {
+        let path = &file.path;
+        let rel_path = path
+            .strip_prefix(&metadata_git_dir)
+            .unwrap_or(path.as_path())
+            .to_string_lossy()
+            .replace('\\', "/");
+        let lastmod = metadata_lastmods
+            .get(&rel_path)
+            .cloned()
+            .or_else(|| metadata_lastmods.get(&file.name).cloned())
+            .ok_or_else(|| anyhow::anyhow!("Missing git lastmod for {:?}", path))?;
+        let lastmod = clamp_sitemap_lastmod(&lastmod, sitemap_min_lastmod.as_ref())?;
+        md5_entries.push((file.id().to_string(), lastmod));
     }
     md5_entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
 
@@ -1017,13 +1118,163 @@ async fn generate_sitemap(dir: &str, site_url: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn get_file_size(id: &str, s3_obj: &[Object]) -> Option<i64> {
-    s3_obj
+/// 提取待发布 PDF 的文字加入全文索引, 并去掉元信息已被删除的文件
+fn update_fulltext_index(
+    pdfium: &Pdfium,
+    corpus: &Corpus,
+    files: &HashSet<TempFiles>,
+    index: &mut FullTextIndex,
+) -> anyhow::Result<()> {
+    println!("Updating full-text index");
+    for file in files {
+        let path = Path::new("./tmp1").join(&file.file_name);
+        if !file.file_name.ends_with(".pdf") || !path.is_file() {
+            continue;
+        }
+        match pdfium
+            .load_pdf_from_file(&path, None)
+            .map_err(anyhow::Error::from)
+            .and_then(|document| extract_text(&document))
+        {
+            Ok(text) => index.add(&file.file_name[..32], &text),
+            Err(e) => println!("Failed to extract text from {}: {:?}", file.file_name, e),
+        }
+    }
+    index.retain(|md5| corpus.contains(md5));
+    println!("Full-text index contains {} files", index.documents.len());
+    Ok(())
+}
+
+/// 已发布但还不在全文索引中的 PDF, 按文件名排序取前 `limit` 个;
+/// 文件索引中记为没有文字层或提取失败的文件不会被选中
+fn fulltext_backfill_keys(
+    s3_obj: &[Object],
+    published: &HashSet<&str>,
+    index: &FullTextIndex,
+    file_index: &FileIndex,
+    limit: usize,
+) -> Vec<String> {
+    let indexed = index
+        .documents
         .iter()
-        .find(|file| {
-            file.key == Some(format!("{}.pdf", id)) || file.key == Some(format!("{}.zip", id))
+        .map(String::as_str)
+        .collect::<HashSet<_>>();
+    let mut keys = s3_obj
+        .iter()
+        .filter_map(|object| object.key.as_deref())
+        .filter(|key| {
+            let Some(md5) = key.strip_suffix(".pdf") else {
+                return false;
+            };
+            published.contains(md5)
+                && !indexed.contains(md5)
+                && !file_index.files.get(md5).is_some_and(|info| {
+                    info.has_text_layer == Some(false) || info.fulltext_failed == Some(true)
+                })
         })
-        .and_then(|file| file.size)
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    keys.sort();
+    keys.truncate(limit);
+    keys
+}
+
+/// 下载文件桶中的一个文件
+async fn download_object(s3_client: &S3Client, bucket: &str, key: &str) -> anyhow::Result<Vec<u8>> {
+    let request = rusoto_s3::GetObjectRequest {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        ..Default::default()
+    };
+    let body = s3_client
+        .get_object(request)
+        .await?
+        .body
+        .ok_or_else(|| anyhow::anyhow!("Empty body"))?;
+    let mut reader = BufReader::new(body.into_async_read());
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).await?;
+    Ok(buf)
+}
+
+/// 为在全文索引之前发布的 PDF 补建索引, 分多次运行逐步完成;
+/// 提取不到文字的文件记为没有文字层, 无法提取的文件记为提取失败, 之后都不再下载.
+/// 下载失败只记录日志, 下次运行时重试
+async fn backfill_fulltext_index(
+    pdfium: &Pdfium,
+    s3_client: &S3Client,
+    bucket: &str,
+    keys: Vec<String>,
+    index: &mut FullTextIndex,
+    file_index: &mut FileIndex,
+) {
+    println!("Backfilling full-text index with {} files", keys.len());
+    for key in keys {
+        let md5 = &key[..32];
+        let buf = match download_object(s3_client, bucket, &key).await {
+            Ok(buf) => buf,
+            Err(e) => {
+                println!("Failed to download {}: {:?}", key, e);
+                continue;
+            }
+        };
+        match pdfium
+            .load_pdf_from_byte_slice(&buf, None)
+            .map_err(anyhow::Error::from)
+            .and_then(|document| extract_text(&document))
+        {
+            Ok(text) => {
+                index.add(md5, &text);
+                if !index.contains(md5) {
+                    file_index.entry(md5).has_text_layer = Some(false);
+                }
+            }
+            Err(e) => {
+                println!("Failed to extract text from {}: {:?}", key, e);
+                file_index.entry(md5).fulltext_failed = Some(true);
+            }
+        }
+    }
+}
+
+/// 为待发布的 Book 提取 PDF 书签, 写入 ./outlines/{md5}.json
+fn extract_book_outlines(
+    pdfium: &Pdfium,
+    corpus: &Corpus,
+    files: &HashSet<TempFiles>,
+) -> anyhow::Result<()> {
+    println!("Extracting book outlines");
+    std::fs::create_dir_all(OUTLINE_DIR)?;
+    for file in files {
+        let md5 = &file.file_name[..32];
+        let path = Path::new("./tmp1").join(&file.file_name);
+        if !file.file_name.ends_with(".pdf")
+            || !path.is_file()
+            || !corpus
+                .get(md5)
+                .is_some_and(|metadata| metadata.type_ == Type::Book)
+        {
+            continue;
+        }
+        let outline = match pdfium.load_pdf_from_file(&path, None) {
+            Ok(document) => extract_outline(&document),
+            Err(e) => {
+                println!("Failed to load pdf {}: {:?}", file.file_name, e);
+                continue;
+            }
+        };
+        if !outline.is_empty() {
+            std::fs::write(
+                Path::new(OUTLINE_DIR).join(format!("{}.json", md5)),
+                serde_json::to_string(&outline)?,
+            )?;
+        }
+    }
+    println!(
+        "{} outline files generated",
+        Path::new(OUTLINE_DIR).read_dir()?.count()
+    );
+    Ok(())
 }
 
 async fn upload_metadata(
@@ -1052,13 +1303,41 @@ async fn upload_metadata(
     let mut buf = Vec::new();
     reader.read_to_end(&mut buf).await?;
     let request = rusoto_s3::PutObjectRequest {
-        bucket,
+        bucket: bucket.clone(),
         key: "sitemap.xml".to_string(),
         body: Some(buf.into()),
         content_type: Some("application/xml".to_string()),
         ..Default::default()
     };
     r2_client.put_object(request).await?;
+    for (key, content_type) in [
+        (FILE_INDEX_NAME, "application/json"),
+        (FULLTEXT_INDEX_NAME, "application/json"),
+        (BIBTEX_NAME, "application/x-bibtex; charset=utf-8"),
+        (CSL_JSON_NAME, "application/vnd.citationstyles.csl+json"),
+        (STATS_JSON_NAME, "application/json"),
+        (STATS_MARKDOWN_NAME, "text/markdown; charset=utf-8"),
+    ] {
+        let request = rusoto_s3::PutObjectRequest {
+            bucket: bucket.clone(),
+            key: key.to_string(),
+            body: Some(std::fs::read(key)?.into()),
+            content_type: Some(content_type.to_string()),
+            ..Default::default()
+        };
+        r2_client.put_object(request).await?;
+    }
+    for file in Path::new(OUTLINE_DIR).read_dir()? {
+        let path = file?.path();
+        let request = rusoto_s3::PutObjectRequest {
+            bucket: bucket.clone(),
+            key: format!("outlines/{}", path.file_name().unwrap().to_string_lossy()),
+            body: Some(std::fs::read(&path)?.into()),
+            content_type: Some("application/json".to_string()),
+            ..Default::default()
+        };
+        r2_client.put_object(request).await?;
+    }
     println!("Metadata uploaded");
     Ok(())
 }
@@ -1169,7 +1448,13 @@ fn get_git_lastmods_for_yaml(
     let output = Command::new("git")
         .arg("-C")
         .arg(repo_root)
-        .args(["log", "--name-only", "--format=__COMMIT__%n%cI", "--"])
+        .args([
+            "log",
+            "-M",
+            "--name-status",
+            "--format=__COMMIT__%n%cI",
+            "--",
+        ])
         .arg(scope)
         .output()?;
     if !output.status.success() {
@@ -1180,8 +1465,15 @@ fn get_git_lastmods_for_yaml(
         ));
     }
 
-    let stdout = String::from_utf8(output.stdout)?;
+    Ok(parse_git_lastmods(&String::from_utf8(output.stdout)?))
+}
+
+/// 从新到旧读取 `git log --name-status` 的输出, 纯重命名 (如移动到分片目录) 不算修改,
+/// 文件继承重命名前的修改时间
+fn parse_git_lastmods(stdout: &str) -> HashMap<String, String> {
     let mut lastmods = HashMap::new();
+    // 旧路径 -> 现在的路径
+    let mut renames: HashMap<String, String> = HashMap::new();
     let mut current_commit_time: Option<String> = None;
     let mut expect_commit_time = false;
 
@@ -1199,23 +1491,60 @@ fn get_git_lastmods_for_yaml(
         if line.is_empty() {
             continue;
         }
-        if !line.ends_with(".yml") {
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let (status, path) = match fields.as_slice() {
+            [status, path] | [status, _, path] => (*status, path.replace('\\', "/")),
+            _ => continue,
+        };
+        if !path.ends_with(".yml") {
+            continue;
+        }
+        let path = renames.get(&path).cloned().unwrap_or(path);
+        if let [_, old_path, _] = fields.as_slice() {
+            renames.insert(old_path.replace('\\', "/"), path.clone());
+        }
+        if status == "R100" {
             continue;
         }
         if let Some(commit_time) = &current_commit_time {
-            lastmods
-                .entry(line.replace('\\', "/"))
-                .or_insert_with(|| commit_time.clone());
+            lastmods.entry(path).or_insert_with(|| commit_time.clone());
         }
     }
 
-    Ok(lastmods)
+    lastmods
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn parse_git_lastmods_follows_pure_renames() {
+        let stdout = "__COMMIT__
+2024-03-01T00:00:00+08:00
+
+R100\ta.yml\tab/a.yml
+R090\tb.yml\tbc/b.yml
+__COMMIT__
+2024-02-01T00:00:00+08:00
+
+M\ta.yml
+A\tc.yml
+M\tb.yml
+__COMMIT__
+2024-01-01T00:00:00+08:00
+
+A\ta.yml
+A\tb.yml
+A\tREADME.md
+";
+        let lastmods = parse_git_lastmods(stdout);
+        assert_eq!(lastmods.len(), 3);
+        assert_eq!(lastmods["ab/a.yml"], "2024-02-01T00:00:00+08:00");
+        assert_eq!(lastmods["bc/b.yml"], "2024-03-01T00:00:00+08:00");
+        assert_eq!(lastmods["c.yml"], "2024-02-01T00:00:00+08:00");
+    }
+
     #[test]
     fn clamp_sitemap_lastmod_replaces_older_dates() {
         let min_lastmod = SitemapMinLastmod::parse("2024-01-01".to_string()).unwrap();
@@ -1260,47 +1589,110 @@ mod tests {
     }
 }
 
-async fn list_all_objects(client: &rusoto_s3::S3Client, bucket: &str) -> Vec<Object> {
-    let mut continuation_token: Option<String> = None;
-    let mut s3_file_list = Vec::new();
+#[cfg(test)]
+mod test {
+    use std::collections::HashSet;
 
-    loop {
-        let request = rusoto_s3::ListObjectsV2Request {
-            bucket: bucket.to_string(),
-            continuation_token: continuation_token.clone(),
-            ..Default::default()
-        };
+    use byrdocs_check::{
+        citation::{to_bibtex, to_csl_json},
+        corpus::{Corpus, Layout},
+        file_index::FileIndex,
+        stats::collect_stats,
+    };
 
-        match client.list_objects_v2(request).await {
-            Ok(output) => {
-                if let Some(contents) = output.contents {
-                    s3_file_list.extend(contents);
-                }
+    use byrdocs_check::fulltext::FullTextIndex;
+    use rusoto_s3::Object;
 
-                if let Some(next_continuation_token) = output.next_continuation_token {
-                    continuation_token = Some(next_continuation_token);
-                } else {
-                    break;
-                }
-            }
-            Err(rusoto_core::RusotoError::Unknown(resp)) => {
-                eprintln!("Error: {}", resp.status);
-                break;
-            }
-            Err(e) => {
-                eprintln!("Error: {:?}", e);
-                break;
-            }
-        }
+    use crate::{fulltext_backfill_keys, generate_zip_preview, merge_json, published_files};
+
+    #[test]
+    fn test_fulltext_backfill_keys() {
+        let md5 = |c: char| c.to_string().repeat(32);
+        let s3_obj = ['a', 'b', 'c', 'd', 'e']
+            .map(|c| format!("{}.pdf", md5(c)))
+            .into_iter()
+            .chain([format!("{}.zip", md5('f')), format!("{}.jpg", md5('a'))])
+            .map(|key| Object {
+                key: Some(key),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let ids = ['a', 'b', 'c', 'd', 'e', 'f'].map(md5);
+        let published = ids.iter().map(String::as_str).collect::<HashSet<_>>();
+        let mut index = FullTextIndex::default();
+        index.add(&md5('a'), "数据结构");
+        let mut file_index = FileIndex::default();
+        file_index.entry(&md5('b')).has_text_layer = Some(false);
+        file_index.entry(&md5('c')).has_text_layer = Some(true);
+        file_index.entry(&md5('d')).fulltext_failed = Some(true);
+
+        assert_eq!(
+            fulltext_backfill_keys(&s3_obj, &published, &index, &file_index, 10),
+            [format!("{}.pdf", md5('c')), format!("{}.pdf", md5('e'))]
+        );
+        assert_eq!(
+            fulltext_backfill_keys(&s3_obj, &published, &index, &file_index, 1),
+            [format!("{}.pdf", md5('c'))]
+        );
     }
-    s3_file_list
-}
 
-#[cfg(test)]
-mod test {
-    use std::collections::HashSet;
+    #[tokio::test]
+    async fn test_blocked_files_are_not_published() {
+        let dir = tempfile::tempdir().unwrap();
+        for (id, title) in [("a".repeat(32), "公开"), ("b".repeat(32), "被阻止")] {
+            std::fs::write(
+                dir.path().join(format!("{}.yml", id)),
+                format!(
+                    "id: {id}\nurl: https://byrdocs.org/files/{id}.pdf\ntype: book\ndata:\n  title: {title}\n  authors: [x]\n  isbn: []\n  filetype: pdf\n"
+                ),
+            )
+            .unwrap();
+        }
+        let corpus = Corpus::load(dir.path(), Layout::Flat).unwrap();
+        let blocked = HashSet::from(["b".repeat(32)]);
+        let dir_name = dir.path().to_str().unwrap();
+        let records = merge_json(&corpus, &blocked, dir_name, &[], &FileIndex::default())
+            .await
+            .unwrap();
+        let metadata_json = std::fs::read_to_string(dir.path().join("metadata.json")).unwrap();
+        let blocked_id = "b".repeat(32);
+        assert_eq!(records.len(), 1);
+        assert!(!metadata_json.contains(&blocked_id));
+        assert!(!to_bibtex(&records).contains(&blocked_id));
+        assert_eq!(collect_stats(&records).total.count, 1);
+        let sitemap_ids = published_files(&corpus, &blocked)
+            .map(|file| file.id())
+            .collect::<Vec<_>>();
+        assert_eq!(sitemap_ids, ["a".repeat(32)]);
+    }
 
-    use crate::generate_zip_preview;
+    #[tokio::test]
+    async fn test_cite_matches_uploaded_citations() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = "c".repeat(32);
+        std::fs::write(
+            dir.path().join(format!("{}.yml", id)),
+            format!(
+                "id: {id}\nurl: https://byrdocs.org/files/{id}.pdf\ntype: book\ndata:\n  title: t\n  authors: [x]\n  isbn: ['9787121155352']\n  filetype: pdf\n"
+            ),
+        )
+        .unwrap();
+        let corpus = Corpus::load(dir.path(), Layout::Flat).unwrap();
+        let uploaded = merge_json(
+            &corpus,
+            &HashSet::new(),
+            dir.path().to_str().unwrap(),
+            &[],
+            &FileIndex::default(),
+        )
+        .await
+        .unwrap();
+        // byrdocs cite 直接使用目录中的元信息
+        let cited = corpus.metadata().cloned().collect::<Vec<_>>();
+        assert_eq!(to_bibtex(&cited), to_bibtex(&uploaded));
+        assert_eq!(to_csl_json(&cited), to_csl_json(&uploaded));
+        assert!(to_bibtex(&cited).contains("isbn = {978-7-121-15535-2}"));
+    }
 
     #[tokio::test]
     #[ignore]
@@ -0,0 +1,193 @@
+use serde_json::{Value, json};
+
+use crate::{hygiene::is_cjk, metadata::*};
+
+pub const BIBTEX_NAME: &str = "books.bib";
+pub const CSL_JSON_NAME: &str = "books.csl.json";
+
+/// 引用键使用文件 md5, 保证在整个语料库中唯一
+fn citation_key(id: &str) -> String {
+    format!("byrdocs-{}", id)
+}
+
+/// 转义 BibTeX 中有特殊含义的字符
+fn escape_bibtex(value: &str) -> String {
+    let mut escaped = String::new();
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '{' | '}' | '&' | '%' | '$' | '#' | '_' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// 中文姓名单独加括号, 避免 BibTeX 把它拆成姓和名
+fn bibtex_names(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| {
+            if name.chars().any(is_cjk) {
+                format!("{{{}}}", escape_bibtex(name))
+            } else {
+                escape_bibtex(name)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" and ")
+}
+
+/// 与 metadata.json 一致, 使用带连字符的 ISBN
+fn isbns(book: &Book) -> String {
+    book.isbn
+        .iter()
+        .map(|isbn| hyphenate_isbn(isbn))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn book_bibtex(id: &str, url: &str, book: &Book) -> String {
+    let mut fields = vec![("title", format!("{{{}}}", escape_bibtex(&book.title)))];
+    if !book.authors.is_empty() {
+        fields.push(("author", bibtex_names(&book.authors)));
+    }
+    if let Some(translators) = book.translators.as_deref().filter(|t| !t.is_empty()) {
+        fields.push(("translator", bibtex_names(translators)));
+    }
+    for (name, value) in [
+        ("edition", &book.edition),
+        ("publisher", &book.publisher),
+        ("year", &book.publish_year),
+    ] {
+        if let Some(value) = value.as_deref().filter(|value| !value.is_empty()) {
+            fields.push((name, escape_bibtex(value)));
+        }
+    }
+    if !book.isbn.is_empty() {
+        fields.push(("isbn", isbns(book)));
+    }
+    fields.push(("url", url.to_string()));
+    let mut entry = format!("@book{{{},\n", citation_key(id));
+    for (name, value) in fields {
+        entry.push_str(&format!("  {} = {{{}}},\n", name, value));
+    }
+    entry.push_str("}\n");
+    entry
+}
+
+pub fn book_csl_json(id: &str, url: &str, book: &Book) -> Value {
+    let names = |names: &[String]| {
+        names
+            .iter()
+            .map(|name| json!({ "literal": name }))
+            .collect::<Vec<_>>()
+    };
+    let mut item = json!({
+        "id": citation_key(id),
+        "type": "book",
+        "title": book.title,
+        "URL": url,
+    });
+    let fields = item.as_object_mut().unwrap();
+    if !book.authors.is_empty() {
+        fields.insert("author".to_string(), names(&book.authors).into());
+    }
+    if let Some(translators) = book.translators.as_deref().filter(|t| !t.is_empty()) {
+        fields.insert("translator".to_string(), names(translators).into());
+    }
+    if let Some(edition) = book.edition.as_deref().filter(|e| !e.is_empty()) {
+        fields.insert("edition".to_string(), edition.into());
+    }
+    if let Some(publisher) = book.publisher.as_deref().filter(|p| !p.is_empty()) {
+        fields.insert("publisher".to_string(), publisher.into());
+    }
+    if let Some(year) = book.publish_year.as_deref().filter(|y| !y.is_empty()) {
+        let issued = match year.parse::<i32>() {
+            Ok(year) => json!({ "date-parts": [[year]] }),
+            Err(_) => json!({ "raw": year }),
+        };
+        fields.insert("issued".to_string(), issued);
+    }
+    if !book.isbn.is_empty() {
+        fields.insert("ISBN".to_string(), isbns(book).into());
+    }
+    item
+}
+
+fn books(records: &[MetaData]) -> impl Iterator<Item = (&MetaData, &Book)> {
+    records.iter().filter_map(|metadata| match &metadata.data {
+        Data::Book(book) => Some((metadata, book)),
+        _ => None,
+    })
+}
+
+/// 所有书籍的 BibTeX 条目, 其他类型的元信息被忽略
+pub fn to_bibtex(records: &[MetaData]) -> String {
+    books(records)
+        .map(|(metadata, book)| book_bibtex(&metadata.id, &metadata.url, book))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// 所有书籍的 CSL-JSON 数组, 其他类型的元信息被忽略
+pub fn to_csl_json(records: &[MetaData]) -> Value {
+    books(records)
+        .map(|(metadata, book)| book_csl_json(&metadata.id, &metadata.url, book))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::records;
+
+    #[test]
+    fn test_to_bibtex() {
+        assert_eq!(
+            to_bibtex(&records()),
+            "@book{byrdocs-c,
+  title = {{C++ Primer 中文版}},
+  author = {Stanley B. Lippman and Josée Lajoie},
+  translator = {{王刚} and {杨巨峰}},
+  edition = {5},
+  publisher = {电子工业出版社},
+  year = {2013},
+  isbn = {978-7-121-15535-2},
+  url = {https://byrdocs.org/files/c.pdf},
+}
+
+@book{byrdocs-e,
+  title = {{50\\% \\& more\\_\\{x\\}}},
+  url = {https://byrdocs.org/files/e.pdf},
+}
+"
+        );
+    }
+
+    #[test]
+    fn test_to_csl_json() {
+        let items = to_csl_json(&records());
+        assert_eq!(
+            items[0],
+            json!({
+                "id": "byrdocs-c",
+                "type": "book",
+                "title": "C++ Primer 中文版",
+                "URL": "https://byrdocs.org/files/c.pdf",
+                "author": [{"literal": "Stanley B. Lippman"}, {"literal": "Josée Lajoie"}],
+                "translator": [{"literal": "王刚"}, {"literal": "杨巨峰"}],
+                "edition": "5",
+                "publisher": "电子工业出版社",
+                "issued": {"date-parts": [[2013]]},
+                "ISBN": "978-7-121-15535-2",
+            })
+        );
+        assert_eq!(items.as_array().unwrap().len(), 2);
+    }
+}
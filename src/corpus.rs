@@ -0,0 +1,389 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{metadata::*, rules::Finding};
+
+/// 分片布局中子目录名取 md5 的前几位
+pub const SHARD_LEN: usize = 2;
+
+/// 元信息目录的布局
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, clap::ValueEnum)]
+pub enum Layout {
+    /// 所有文件都在根目录下
+    #[default]
+    Flat,
+    /// 文件可以放在任意子目录中
+    Nested,
+    /// 文件放在以 md5 前两位命名的子目录中, 如 `ab/abcdef....yml`
+    Sharded,
+}
+
+impl Layout {
+    /// 解析 `METADATA_LAYOUT` 环境变量, 未设置时为 `flat`
+    pub fn from_env() -> anyhow::Result<Self> {
+        match crate::get_optional_env("METADATA_LAYOUT") {
+            Some(value) => <Self as clap::ValueEnum>::from_str(&value, true).map_err(|_| {
+                anyhow::anyhow!(
+                    "Invalid METADATA_LAYOUT: {value}. Expected flat, nested or sharded."
+                )
+            }),
+            None => Ok(Self::default()),
+        }
+    }
+
+    pub fn is_recursive(self) -> bool {
+        self != Layout::Flat
+    }
+
+    /// 该布局下新建文件时使用的相对路径
+    pub fn file_name(self, id: &str) -> String {
+        match self {
+            Layout::Flat | Layout::Nested => format!("{}.yml", id),
+            Layout::Sharded => format!("{}/{}.yml", shard(id), id),
+        }
+    }
+
+    /// 该布局下新建文件时使用的路径
+    pub fn path(self, root: &Path, id: &str) -> PathBuf {
+        root.join(self.file_name(id))
+    }
+
+    /// 分片布局中检查文件是否在对应的子目录中
+    pub fn check_location(self, file: &CorpusFile) -> Option<Finding> {
+        let expected = match self {
+            Layout::Sharded => self.file_name(file.id()),
+            Layout::Flat | Layout::Nested => return None,
+        };
+        (file.name != expected)
+            .then(|| Finding::new("shard-location", format!("请将文件移动到 {}", expected)))
+    }
+}
+
+fn shard(id: &str) -> String {
+    id.chars()
+        .take(SHARD_LEN)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// 目录中的一个元信息文件, 无法解析时保留错误
+#[derive(Debug)]
+pub struct CorpusFile {
+    pub path: PathBuf,
+    /// 相对于根目录的路径, 以 `/` 分隔
+    pub name: String,
+    pub bytes: Vec<u8>,
+    pub metadata: Result<MetaData, serde_yaml::Error>,
+}
+
+impl CorpusFile {
+    /// 文件名去掉 `.yml`, 即文件的 md5
+    pub fn id(&self) -> &str {
+        let file_name = self.name.rsplit('/').next().unwrap_or(&self.name);
+        file_name.strip_suffix(".yml").unwrap_or(file_name)
+    }
+
+    /// 不是有效的 UTF-8 时为空
+    pub fn source(&self) -> Option<&str> {
+        std::str::from_utf8(&self.bytes).ok()
+    }
+}
+
+/// 一次性读取的元信息目录, 按 id、isbn、课程和类型建立索引
+#[derive(Debug, Default)]
+pub struct Corpus {
+    pub root: PathBuf,
+    pub layout: Layout,
+    /// 所有 `.yml` 文件, 按 `name` 排序
+    pub files: Vec<CorpusFile>,
+    /// 其他文件和目录的相对路径, 按名称排序
+    pub other_files: Vec<String>,
+    by_id: HashMap<String, usize>,
+    /// 出现在多个文件中的 id, 对应所有这些文件
+    duplicate_ids: HashMap<String, Vec<usize>>,
+    by_isbn: HashMap<isbn::Isbn13, Vec<usize>>,
+    by_course: HashMap<String, Vec<usize>>,
+}
+
+impl Corpus {
+    /// 读取目录中的元信息, 非平铺布局时也读取子目录 (跳过以 `.` 开头的目录)
+    pub fn load(root: &Path, layout: Layout) -> anyhow::Result<Self> {
+        let mut corpus = Corpus {
+            root: root.to_path_buf(),
+            layout,
+            ..Default::default()
+        };
+        corpus.visit(root, "", layout.is_recursive())?;
+        corpus.files.sort_by(|a, b| a.name.cmp(&b.name));
+        corpus.other_files.sort();
+        corpus.build_index();
+        Ok(corpus)
+    }
+
+    fn visit(&mut self, dir: &Path, prefix: &str, recursive: bool) -> anyhow::Result<()> {
+        for entry in dir.read_dir()? {
+            let entry = entry?;
+            let path = entry.path();
+            let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+            if path.is_dir() && recursive {
+                let hidden = entry.file_name().to_string_lossy().starts_with('.');
+                if !hidden {
+                    self.visit(&path, &format!("{}/", name), recursive)?;
+                }
+            } else if path.is_dir() {
+                if !name.ends_with(".yml") {
+                    self.other_files.push(name);
+                }
+            } else if name.ends_with(".yml") {
+                let bytes = std::fs::read(&path)?;
+                let metadata = serde_yaml::from_slice(&bytes);
+                self.files.push(CorpusFile {
+                    path,
+                    name,
+                    bytes,
+                    metadata,
+                });
+            } else {
+                self.other_files.push(name);
+            }
+        }
+        Ok(())
+    }
+
+    fn build_index(&mut self) {
+        for (index, file) in self.files.iter().enumerate() {
+            if let Some(&first) = self.by_id.get(file.id()) {
+                self.duplicate_ids
+                    .entry(file.id().to_string())
+                    .or_insert_with(|| vec![first])
+                    .push(index);
+            } else {
+                self.by_id.insert(file.id().to_string(), index);
+            }
+            let Ok(metadata) = &file.metadata else {
+                continue;
+            };
+            match &metadata.data {
+                Data::Book(book) => {
+                    for isbn in &book.isbn {
+                        if let Ok(isbn) = isbn.parse::<isbn::Isbn13>() {
+                            self.by_isbn.entry(isbn).or_default().push(index);
+                        }
+                    }
+                }
+                Data::Test(test) => {
+                    self.by_course
+                        .entry(test.course.name.clone())
+                        .or_default()
+                        .push(index);
+                }
+                Data::Doc(doc) => {
+                    for course in &doc.course {
+                        self.by_course
+                            .entry(course.name.clone())
+                            .or_default()
+                            .push(index);
+                    }
+                }
+            }
+        }
+    }
+
+    fn collect(&self, indexes: Option<&Vec<usize>>) -> Vec<&MetaData> {
+        indexes
+            .into_iter()
+            .flatten()
+            .filter_map(|&index| self.files[index].metadata.as_ref().ok())
+            .collect()
+    }
+
+    /// 是否有该 id 的元信息文件, 包括无法解析的文件
+    pub fn contains(&self, id: &str) -> bool {
+        self.by_id.contains_key(id)
+    }
+
+    pub fn file(&self, id: &str) -> Option<&CorpusFile> {
+        self.by_id.get(id).map(|&index| &self.files[index])
+    }
+
+    /// 非平铺布局中不同子目录下有同名文件时, 报告除自身以外的文件
+    pub fn check_duplicate_id(&self, file: &CorpusFile) -> Option<Finding> {
+        let others = self
+            .duplicate_ids
+            .get(file.id())?
+            .iter()
+            .map(|&index| self.files[index].name.as_str())
+            .filter(|&name| name != file.name)
+            .collect::<Vec<_>>();
+        Some(Finding::new(
+            "duplicate-id",
+            format!("其他文件的id与此文件相同: {}", others.join(", ")),
+        ))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&MetaData> {
+        self.file(id)?.metadata.as_ref().ok()
+    }
+
+    /// 所有元信息文件的 id, 包括无法解析的文件
+    pub fn ids(&self) -> impl Iterator<Item = &str> {
+        self.files.iter().map(CorpusFile::id)
+    }
+
+    /// 所有成功解析的元信息
+    pub fn metadata(&self) -> impl Iterator<Item = &MetaData> {
+        self.files
+            .iter()
+            .filter_map(|file| file.metadata.as_ref().ok())
+    }
+
+    /// 无法解析的文件和对应的错误
+    pub fn errors(&self) -> impl Iterator<Item = (&CorpusFile, &serde_yaml::Error)> {
+        self.files
+            .iter()
+            .filter_map(|file| file.metadata.as_ref().err().map(|e| (file, e)))
+    }
+
+    /// 有任何文件无法解析时返回第一个错误
+    pub fn ensure_parsed(&self) -> anyhow::Result<()> {
+        match self.errors().next() {
+            Some((file, e)) => Err(anyhow::anyhow!("Failed to parse {:?}: {}", file.path, e)),
+            None => Ok(()),
+        }
+    }
+
+    /// 按 isbn 查找书籍, 带或不带连字符、ISBN-10 或 ISBN-13 均可
+    pub fn find_isbn(&self, isbn: &str) -> Vec<&MetaData> {
+        let isbn = match isbn.parse::<isbn::Isbn>() {
+            Ok(isbn::Isbn::_10(isbn)) => isbn::Isbn13::from(isbn),
+            Ok(isbn::Isbn::_13(isbn)) => isbn,
+            Err(_) => return Vec::new(),
+        };
+        self.collect(self.by_isbn.get(&isbn))
+    }
+
+    /// 按课程名查找试卷和资料
+    pub fn find_course(&self, course: &str) -> Vec<&MetaData> {
+        self.collect(self.by_course.get(course))
+    }
+
+    pub fn of_type(&self, type_: Type) -> impl Iterator<Item = &MetaData> {
+        self.metadata()
+            .filter(move |metadata| metadata.type_ == type_)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_corpus() {
+        let dir = tempfile::tempdir().unwrap();
+        let write = |name: &str, source: &str| {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        };
+        write(
+            "b.yml",
+            "id: b\nurl: u\ntype: book\ndata:\n  title: t\n  authors: [a]\n  isbn: [978-7-111-40772-0]\n  filetype: pdf\n",
+        );
+        write(
+            "sub/t.yml",
+            "id: t\nurl: u\ntype: test\ndata:\n  course: {name: 数据结构}\n  time: {start: '2023', end: '2024'}\n  filetype: pdf\n  content: [原题]\n",
+        );
+        write("broken.yml", "id: [");
+        write("notes.txt", "");
+        write(".git/config", "");
+
+        let corpus = Corpus::load(dir.path(), Layout::Flat).unwrap();
+        assert_eq!(corpus.ids().collect::<Vec<_>>(), ["b", "broken"]);
+        assert_eq!(corpus.other_files, [".git", "notes.txt", "sub"]);
+        assert!(corpus.contains("broken"));
+        assert!(corpus.get("broken").is_none());
+        assert_eq!(corpus.errors().count(), 1);
+        assert!(corpus.ensure_parsed().is_err());
+        assert_eq!(corpus.find_isbn("7111407725")[0].id, "b");
+        assert_eq!(corpus.find_isbn("9787111407720")[0].id, "b");
+
+        let corpus = Corpus::load(dir.path(), Layout::Nested).unwrap();
+        assert_eq!(corpus.ids().collect::<Vec<_>>(), ["b", "broken", "t"]);
+        assert_eq!(corpus.file("t").unwrap().name, "sub/t.yml");
+        assert_eq!(corpus.other_files, ["notes.txt"]);
+        assert_eq!(corpus.find_course("数据结构")[0].id, "t");
+        assert_eq!(corpus.of_type(Type::Book).count(), 1);
+        assert_eq!(corpus.metadata().count(), 2);
+        assert!(
+            corpus
+                .files
+                .iter()
+                .all(|file| Layout::Nested.check_location(file).is_none())
+        );
+        assert!(
+            corpus
+                .files
+                .iter()
+                .all(|file| corpus.check_duplicate_id(file).is_none())
+        );
+
+        write("other/t.yml", "id: [");
+        let corpus = Corpus::load(dir.path(), Layout::Nested).unwrap();
+        assert_eq!(corpus.file("t").unwrap().name, "other/t.yml");
+        let findings = corpus
+            .files
+            .iter()
+            .filter_map(|file| Some((file.name.as_str(), corpus.check_duplicate_id(file)?.message)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                (
+                    "other/t.yml",
+                    "其他文件的id与此文件相同: sub/t.yml".to_string()
+                ),
+                (
+                    "sub/t.yml",
+                    "其他文件的id与此文件相同: other/t.yml".to_string()
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_sharded_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let id = "0123456789abcdef0123456789abcdef";
+        assert_eq!(Layout::Sharded.file_name(id), format!("01/{}.yml", id));
+        assert_eq!(Layout::Flat.file_name(id), format!("{}.yml", id));
+        for name in [
+            format!("01/{}.yml", id),
+            "ab/cd.yml".to_string(),
+            "ef.yml".to_string(),
+        ] {
+            let path = dir.path().join(name);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "").unwrap();
+        }
+        let corpus = Corpus::load(dir.path(), Layout::Sharded).unwrap();
+        let findings = corpus
+            .files
+            .iter()
+            .filter_map(|file| {
+                Some((
+                    file.name.as_str(),
+                    Layout::Sharded.check_location(file)?.message,
+                ))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                ("ab/cd.yml", "请将文件移动到 cd/cd.yml".to_string()),
+                ("ef.yml", "请将文件移动到 ef/ef.yml".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,264 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use serde::Serialize;
+
+use crate::metadata::*;
+
+pub const QUESTIONS: &str = "原题";
+pub const ANSWERS: &str = "答案";
+/// 默认检查最近几个学年是否有试卷
+pub const DEFAULT_RECENT_YEARS: i32 = 3;
+
+/// 一个学年中的一次考试, 学年以 `time.start` 计, 没有填写 stage 时为空
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Term {
+    pub start: i32,
+    pub stage: Option<String>,
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}-{}", self.start, self.start + 1)?;
+        if let Some(stage) = &self.stage {
+            write!(f, " {}", stage)?;
+        }
+        Ok(())
+    }
+}
+
+/// 一门课程的缺口, 各列表按时间排序
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct CourseGaps {
+    pub course: String,
+    /// 有原题但没有答案
+    pub missing_answers: Vec<Term>,
+    /// 有答案但没有原题
+    pub missing_questions: Vec<Term>,
+    /// 最近几个学年中没有任何试卷
+    pub empty_terms: Vec<Term>,
+}
+
+impl CourseGaps {
+    fn is_empty(&self) -> bool {
+        self.missing_answers.is_empty()
+            && self.missing_questions.is_empty()
+            && self.empty_terms.is_empty()
+    }
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Coverage {
+    /// 检查是否有试卷的学年, 以 `time.start` 计
+    pub recent_years: Vec<i32>,
+    /// 只包含有缺口的课程, 按课程名排序
+    pub courses: Vec<CourseGaps>,
+}
+
+#[derive(Default)]
+struct Slot {
+    questions: bool,
+    answers: bool,
+}
+
+/// 只根据试卷的课程、时间和内容统计, 无法解析 `time.start` 的试卷被忽略
+///
+/// 课程有填写 stage 的试卷时, 最近的每个学年都需要有这些 stage 的试卷,
+/// 同一学年中没有 stage 的试卷视为覆盖了所有 stage
+pub fn find_gaps(records: &[MetaData], recent_years: i32, latest_year: Option<i32>) -> Coverage {
+    let mut courses: BTreeMap<&str, BTreeMap<Term, Slot>> = BTreeMap::new();
+    for metadata in records {
+        let Data::Test(test) = &metadata.data else {
+            continue;
+        };
+        let Ok(start) = test.time.start.parse() else {
+            continue;
+        };
+        let term = Term {
+            start,
+            stage: test.time.stage.clone(),
+        };
+        let slot = courses
+            .entry(&test.course.name)
+            .or_default()
+            .entry(term)
+            .or_default();
+        slot.questions |= test.content.iter().any(|c| c == QUESTIONS);
+        slot.answers |= test.content.iter().any(|c| c == ANSWERS);
+    }
+
+    let latest_year = latest_year.or_else(|| {
+        courses
+            .values()
+            .flat_map(|terms| terms.keys().map(|term| term.start))
+            .max()
+    });
+    let recent_years = match latest_year {
+        Some(latest) => (latest - recent_years + 1..=latest).collect(),
+        None => Vec::new(),
+    };
+
+    let mut gaps = Vec::new();
+    for (course, terms) in courses {
+        let mut course_gaps = CourseGaps {
+            course: course.to_string(),
+            ..Default::default()
+        };
+        for (term, slot) in &terms {
+            if slot.questions && !slot.answers {
+                course_gaps.missing_answers.push(term.clone());
+            }
+            if slot.answers && !slot.questions {
+                course_gaps.missing_questions.push(term.clone());
+            }
+        }
+        let stages = terms
+            .keys()
+            .filter_map(|term| term.stage.clone())
+            .collect::<BTreeSet<_>>();
+        let expected = if stages.is_empty() {
+            vec![None]
+        } else {
+            stages.into_iter().map(Some).collect()
+        };
+        for &start in &recent_years {
+            for stage in &expected {
+                let covered = terms.keys().any(|term| {
+                    term.start == start && (term.stage.is_none() || &term.stage == stage)
+                });
+                if !covered {
+                    course_gaps.empty_terms.push(Term {
+                        start,
+                        stage: stage.clone(),
+                    });
+                }
+            }
+        }
+        if !course_gaps.is_empty() {
+            gaps.push(course_gaps);
+        }
+    }
+    Coverage {
+        recent_years,
+        courses: gaps,
+    }
+}
+
+fn join_terms(terms: &[Term]) -> String {
+    terms
+        .iter()
+        .map(Term::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+pub fn to_markdown(coverage: &Coverage) -> String {
+    let mut markdown = String::from("# 试卷覆盖情况\n\n");
+    if let (Some(first), Some(last)) = (coverage.recent_years.first(), coverage.recent_years.last())
+    {
+        markdown.push_str(&format!(
+            "“没有试卷”只检查 {}-{} 至 {}-{} 学年。\n\n",
+            first,
+            first + 1,
+            last,
+            last + 1
+        ));
+    }
+    if coverage.courses.is_empty() {
+        markdown.push_str("所有课程都没有缺口。\n");
+        return markdown;
+    }
+    markdown.push_str("| 课程 | 缺少答案 | 缺少原题 | 没有试卷 |\n| --- | --- | --- | --- |\n");
+    for course in &coverage.courses {
+        markdown.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            course.course.replace('|', "\\|"),
+            join_terms(&course.missing_answers),
+            join_terms(&course.missing_questions),
+            join_terms(&course.empty_terms)
+        ));
+    }
+    markdown
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test(course: &str, start: i32, stage: Option<&str>, content: &[&str]) -> MetaData {
+        let stage = stage
+            .map(|stage| format!(", stage: {}", stage))
+            .unwrap_or_default();
+        let source = format!(
+            "id: x\nurl: u\ntype: test\ndata:\n  course: {{name: {}}}\n  time: {{start: '{}', end: '{}'{}}}\n  filetype: pdf\n  content: [{}]\n",
+            course,
+            start,
+            start + 1,
+            stage,
+            content.join(", ")
+        );
+        serde_yaml::from_str(&source).unwrap()
+    }
+
+    fn term(start: i32, stage: Option<&str>) -> Term {
+        Term {
+            start,
+            stage: stage.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_find_gaps() {
+        let records = [
+            test("数据结构", 2021, Some("期末"), &["原题"]),
+            test("数据结构", 2022, Some("期中"), &["原题", "答案"]),
+            test("数据结构", 2022, Some("期末"), &["原题"]),
+            test("数据结构", 2022, Some("期末"), &["答案"]),
+            test("数据结构", 2023, None, &["答案"]),
+            test("高等数学A", 2023, None, &["原题", "答案"]),
+            test("计算机网络", 2023, Some("期末"), &["原题", "答案"]),
+        ];
+        let coverage = find_gaps(&records, 3, None);
+        assert_eq!(coverage.recent_years, [2021, 2022, 2023]);
+        assert_eq!(
+            coverage.courses,
+            [
+                CourseGaps {
+                    course: "数据结构".to_string(),
+                    missing_answers: vec![term(2021, Some("期末"))],
+                    missing_questions: vec![term(2023, None)],
+                    empty_terms: vec![term(2021, Some("期中"))],
+                },
+                CourseGaps {
+                    course: "计算机网络".to_string(),
+                    empty_terms: vec![term(2021, Some("期末")), term(2022, Some("期末"))],
+                    ..Default::default()
+                },
+                CourseGaps {
+                    course: "高等数学A".to_string(),
+                    empty_terms: vec![term(2021, None), term(2022, None)],
+                    ..Default::default()
+                },
+            ]
+        );
+
+        let coverage = find_gaps(&records, 1, Some(2024));
+        assert_eq!(coverage.courses.len(), 3);
+        assert_eq!(
+            coverage.courses[0].empty_terms,
+            [term(2024, Some("期中")), term(2024, Some("期末"))]
+        );
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let records = [test("数据结构", 2023, Some("期末"), &["原题"])];
+        assert_eq!(
+            to_markdown(&find_gaps(&records, 1, None)),
+            "# 试卷覆盖情况\n\n“没有试卷”只检查 2023-2024 至 2023-2024 学年。\n\n| 课程 | 缺少答案 | 缺少原题 | 没有试卷 |\n| --- | --- | --- | --- |\n| 数据结构 | 2023-2024 期末 |  |  |\n"
+        );
+        assert!(to_markdown(&find_gaps(&[], 3, None)).ends_with("所有课程都没有缺口。\n"));
+    }
+}
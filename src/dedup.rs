@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+
+use crate::{metadata::*, rules::Finding};
+
+/// isbn 按数字比较, 其余字段按原文比较
+fn value_key(value: &str) -> String {
+    match value.parse::<isbn::Isbn13>() {
+        Ok(isbn) => isbn.to_string(),
+        Err(_) => value.to_string(),
+    }
+}
+
+fn check_list<T, K: Eq + std::hash::Hash>(
+    field: &str,
+    values: &[T],
+    key: impl Fn(&T) -> K,
+    display: impl Fn(&T) -> String,
+) -> Vec<Finding> {
+    let mut seen = HashSet::new();
+    values
+        .iter()
+        .filter(|value| !seen.insert(key(value)))
+        .map(|value| {
+            Finding::new(
+                "duplicate-value",
+                format!("{}中存在重复的值: {}", field, display(value)),
+            )
+            .with_key(field)
+        })
+        .collect()
+}
+
+fn check_strings(field: &str, values: &[String]) -> Vec<Finding> {
+    check_list(
+        field,
+        values,
+        |value| value_key(value),
+        |value| format!("{:?}", value),
+    )
+}
+
+/// 检查元信息中每个列表字段是否有重复的值
+pub fn check_duplicate_values(data: &Data) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    match data {
+        Data::Test(test) => {
+            findings.extend(check_strings(
+                "college",
+                test.college.as_deref().unwrap_or_default(),
+            ));
+            findings.extend(check_strings("content", &test.content));
+        }
+        Data::Book(book) => {
+            findings.extend(check_strings("authors", &book.authors));
+            findings.extend(check_strings(
+                "translators",
+                book.translators.as_deref().unwrap_or_default(),
+            ));
+            findings.extend(check_strings("isbn", &book.isbn));
+        }
+        Data::Doc(doc) => {
+            findings.extend(check_list(
+                "course",
+                &doc.course,
+                |course| (course.type_.clone(), course.name.clone()),
+                |course| match &course.type_ {
+                    Some(type_) => format!("{:?}({})", course.name, type_),
+                    None => format!("{:?}", course.name),
+                },
+            ));
+            findings.extend(check_strings("content", &doc.content));
+        }
+    }
+    findings
+}
+
+/// 在源文件上去掉重复的值, 没有需要修复的内容时返回 None
+pub fn fix_duplicate_values(source: &str) -> Option<String> {
+    let fixed = crate::fix::dedup_sequences(source, value_key);
+    (fixed != source).then_some(fixed)
+}
+
+/// 判断两份试卷是否相同的依据: 课程、学年、学期、阶段和内容
+fn test_key(test: &Test) -> impl Ord {
+    let mut content = test.content.clone();
+    content.sort();
+    content.dedup();
+    (
+        test.course.name.trim().to_string(),
+        test.course.type_.clone(),
+        test.time.start.clone(),
+        test.time.end.clone(),
+        test.time.semester.clone(),
+        test.time.stage.clone(),
+        content,
+    )
+}
+
+/// 找出课程、时间、阶段和内容都相同的试卷, 返回每组中所有试卷的键 (如 id), 组内按键排序
+///
+/// 同一个 id 可能出现在多个文件中, 需要区分文件时键中应包含文件的位置
+pub fn find_duplicate_tests<'a, K: Ord>(
+    tests: impl IntoIterator<Item = (K, &'a Test)>,
+) -> Vec<Vec<K>> {
+    let mut groups = std::collections::BTreeMap::new();
+    for (key, test) in tests {
+        groups
+            .entry(test_key(test))
+            .or_insert_with(Vec::new)
+            .push(key);
+    }
+    groups
+        .into_values()
+        .filter(|keys| keys.len() > 1)
+        .map(|mut keys| {
+            keys.sort();
+            keys
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_duplicate_values() {
+        let metadata: MetaData = serde_yaml::from_str(
+            "id: a\nurl: b\ntype: book\ndata:\n  title: t\n  authors: [张三, 李四, 张三]\n  isbn: [978-7-111-40772-0, '9787111407720']\n  filetype: pdf\n",
+        )
+        .unwrap();
+        let findings = check_duplicate_values(&metadata.data);
+        assert_eq!(findings.len(), 2);
+        assert!(findings.iter().all(|f| f.rule == "duplicate-value"));
+
+        let metadata: MetaData = serde_yaml::from_str(
+            "id: a\nurl: b\ntype: doc\ndata:\n  title: t\n  filetype: pdf\n  course:\n    - name: a\n    - name: a\n      type: 本科\n  content: [课件]\n",
+        )
+        .unwrap();
+        assert!(check_duplicate_values(&metadata.data).is_empty());
+    }
+
+    #[test]
+    fn test_fix_duplicate_values() {
+        let source = "data:\n  isbn:\n    - 978-7-111-40772-0\n    - '9787111407720'\n";
+        assert_eq!(
+            fix_duplicate_values(source).unwrap(),
+            "data:\n  isbn:\n    - 978-7-111-40772-0\n"
+        );
+        assert!(fix_duplicate_values("data:\n  content: [原题, 答案]\n").is_none());
+    }
+
+    #[test]
+    fn test_find_duplicate_tests() {
+        let test = |stage: &str, content: &str| -> Test {
+            serde_yaml::from_str(&format!(
+                "course:\n  name: 数据结构\ntime:\n  start: '2023'\n  end: '2024'\n  stage: {stage}\nfiletype: pdf\ncontent: {content}\n"
+            ))
+            .unwrap()
+        };
+        let a = test("期末", "[原题, 答案]");
+        let b = test("期末", "[答案, 原题]");
+        let c = test("期中", "[原题, 答案]");
+        let d = test("期末", "[原题]");
+        let groups = find_duplicate_tests([("b", &b), ("a", &a), ("c", &c), ("d", &d)]);
+        assert_eq!(groups, [["a", "b"]]);
+
+        // 两个文件使用同一个 id 时, 带上文件的位置才能区分
+        let groups = find_duplicate_tests([(("a", 1), &b), (("a", 0), &a), (("a", 2), &c)]);
+        assert_eq!(groups, [[("a", 0), ("a", 1)]]);
+    }
+}
@@ -0,0 +1,290 @@
+use std::io::Write;
+
+use rusqlite::{Connection, params};
+
+use crate::metadata::*;
+
+/// 导出 CSV 的列, 列表字段中的多个值用 `; ` 连接
+pub const CSV_HEADERS: &[&str] = &[
+    "id",
+    "url",
+    "type",
+    "title",
+    "authors",
+    "translators",
+    "edition",
+    "publish_year",
+    "publisher",
+    "isbn",
+    "course",
+    "course_type",
+    "college",
+    "start",
+    "end",
+    "semester",
+    "stage",
+    "content",
+    "filetype",
+    "filesize",
+];
+
+const SCHEMA: &str = "
+CREATE TABLE records (
+    id TEXT PRIMARY KEY,
+    url TEXT NOT NULL,
+    type TEXT NOT NULL,
+    title TEXT,
+    edition TEXT,
+    publish_year TEXT,
+    publisher TEXT,
+    start TEXT,
+    end TEXT,
+    semester TEXT,
+    stage TEXT,
+    filetype TEXT NOT NULL,
+    filesize INTEGER
+);
+CREATE TABLE courses (
+    record_id TEXT NOT NULL REFERENCES records(id),
+    name TEXT NOT NULL,
+    type TEXT
+);
+CREATE TABLE authors (
+    record_id TEXT NOT NULL REFERENCES records(id),
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    role TEXT NOT NULL
+);
+CREATE TABLE isbns (
+    record_id TEXT NOT NULL REFERENCES records(id),
+    isbn TEXT NOT NULL
+);
+CREATE TABLE colleges (
+    record_id TEXT NOT NULL REFERENCES records(id),
+    name TEXT NOT NULL
+);
+CREATE TABLE contents (
+    record_id TEXT NOT NULL REFERENCES records(id),
+    content TEXT NOT NULL
+);
+CREATE INDEX courses_name ON courses(name);
+CREATE INDEX isbns_isbn ON isbns(isbn);
+";
+
+/// 三种类型的元信息展开后的公共视图, 不存在的字段为空
+#[derive(Default)]
+pub(crate) struct Flat<'a> {
+    pub(crate) type_: &'static str,
+    pub(crate) title: Option<&'a str>,
+    pub(crate) authors: &'a [String],
+    pub(crate) translators: &'a [String],
+    pub(crate) edition: Option<&'a str>,
+    pub(crate) publish_year: Option<&'a str>,
+    pub(crate) publisher: Option<&'a str>,
+    pub(crate) isbn: &'a [String],
+    pub(crate) courses: Vec<&'a Course>,
+    pub(crate) colleges: &'a [String],
+    pub(crate) time: Option<&'a Time>,
+    pub(crate) content: &'a [String],
+    pub(crate) filetype: &'a str,
+    pub(crate) filesize: Option<i64>,
+}
+
+pub(crate) fn flatten(metadata: &MetaData) -> Flat<'_> {
+    match &metadata.data {
+        Data::Test(test) => Flat {
+            type_: "test",
+            courses: vec![&test.course],
+            colleges: test.college.as_deref().unwrap_or_default(),
+            time: Some(&test.time),
+            content: &test.content,
+            filetype: &test.filetype,
+            filesize: test.filesize,
+            ..Default::default()
+        },
+        Data::Book(book) => Flat {
+            type_: "book",
+            title: Some(&book.title),
+            authors: &book.authors,
+            translators: book.translators.as_deref().unwrap_or_default(),
+            edition: book.edition.as_deref(),
+            publish_year: book.publish_year.as_deref(),
+            publisher: book.publisher.as_deref(),
+            isbn: &book.isbn,
+            filetype: &book.filetype,
+            filesize: book.filesize,
+            ..Default::default()
+        },
+        Data::Doc(doc) => Flat {
+            type_: "doc",
+            title: Some(&doc.title),
+            courses: doc.course.iter().collect(),
+            content: &doc.content,
+            filetype: &doc.filetype,
+            filesize: doc.filesize,
+            ..Default::default()
+        },
+    }
+}
+
+/// 每条元信息一行, 资料的多门课程按顺序连接, 课程类型去重后连接
+pub fn write_csv(records: &[MetaData], writer: impl Write) -> anyhow::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record(CSV_HEADERS)?;
+    for metadata in records {
+        let flat = flatten(metadata);
+        let mut course_types = Vec::new();
+        for type_ in flat
+            .courses
+            .iter()
+            .filter_map(|course| course.type_.as_deref())
+        {
+            if !course_types.contains(&type_) {
+                course_types.push(type_);
+            }
+        }
+        let time = |f: fn(&Time) -> Option<&str>| flat.time.and_then(f).unwrap_or_default();
+        writer.write_record([
+            metadata.id.as_str(),
+            &metadata.url,
+            flat.type_,
+            flat.title.unwrap_or_default(),
+            &flat.authors.join("; "),
+            &flat.translators.join("; "),
+            flat.edition.unwrap_or_default(),
+            flat.publish_year.unwrap_or_default(),
+            flat.publisher.unwrap_or_default(),
+            &flat.isbn.join("; "),
+            &flat
+                .courses
+                .iter()
+                .map(|course| course.name.as_str())
+                .collect::<Vec<_>>()
+                .join("; "),
+            &course_types.join("; "),
+            &flat.colleges.join("; "),
+            time(|time| Some(&time.start)),
+            time(|time| Some(&time.end)),
+            time(|time| time.semester.as_deref()),
+            time(|time| time.stage.as_deref()),
+            &flat.content.join("; "),
+            flat.filetype,
+            &flat
+                .filesize
+                .map(|size| size.to_string())
+                .unwrap_or_default(),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// 写入规范化的 SQLite 数据库, `connection` 应指向一个空数据库
+pub fn write_sqlite(records: &[MetaData], connection: &mut Connection) -> anyhow::Result<()> {
+    let transaction = connection.transaction()?;
+    transaction.execute_batch(SCHEMA)?;
+    {
+        let mut insert_record = transaction.prepare(
+            "INSERT INTO records VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
+        )?;
+        let mut insert_course = transaction.prepare("INSERT INTO courses VALUES (?1, ?2, ?3)")?;
+        let mut insert_author =
+            transaction.prepare("INSERT INTO authors VALUES (?1, ?2, ?3, ?4)")?;
+        let mut insert_isbn = transaction.prepare("INSERT INTO isbns VALUES (?1, ?2)")?;
+        let mut insert_college = transaction.prepare("INSERT INTO colleges VALUES (?1, ?2)")?;
+        let mut insert_content = transaction.prepare("INSERT INTO contents VALUES (?1, ?2)")?;
+        for metadata in records {
+            let flat = flatten(metadata);
+            let id = &metadata.id;
+            insert_record.execute(params![
+                id,
+                metadata.url,
+                flat.type_,
+                flat.title,
+                flat.edition,
+                flat.publish_year,
+                flat.publisher,
+                flat.time.map(|time| &time.start),
+                flat.time.map(|time| &time.end),
+                flat.time.and_then(|time| time.semester.as_deref()),
+                flat.time.and_then(|time| time.stage.as_deref()),
+                flat.filetype,
+                flat.filesize,
+            ])?;
+            for course in &flat.courses {
+                insert_course.execute(params![id, course.name, course.type_])?;
+            }
+            let people = flat
+                .authors
+                .iter()
+                .map(|name| (name, "author"))
+                .chain(flat.translators.iter().map(|name| (name, "translator")));
+            for (position, (name, role)) in people.enumerate() {
+                insert_author.execute(params![id, position as i64, name, role])?;
+            }
+            for isbn in flat.isbn {
+                insert_isbn.execute(params![id, isbn])?;
+            }
+            for college in flat.colleges {
+                insert_college.execute(params![id, college])?;
+            }
+            for content in flat.content {
+                insert_content.execute(params![id, content])?;
+            }
+        }
+    }
+    transaction.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::records;
+
+    #[test]
+    fn test_write_csv() {
+        let mut output = Vec::new();
+        write_csv(&records(), &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines = output.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], CSV_HEADERS.join(","));
+        assert_eq!(
+            lines[1],
+            "a,https://byrdocs.org/files/a.pdf,test,,,,,,,,数据结构,本科,计算机学院,2022,2023,,期末,原题; 答案,pdf,1024"
+        );
+        assert_eq!(
+            lines[3],
+            "c,https://byrdocs.org/files/c.pdf,book,C++ Primer 中文版,Stanley B. Lippman; Josée Lajoie,王刚; 杨巨峰,5,2013,电子工业出版社,978-7-121-15535-2,,,,,,,,,pdf,3145728"
+        );
+        assert_eq!(
+            lines[4],
+            "d,https://byrdocs.org/files/d.pdf,doc,复习资料,,,,,,,高等数学A; 数据结构,本科,,,,,,知识点,pdf,"
+        );
+    }
+
+    #[test]
+    fn test_write_sqlite() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        write_sqlite(&records(), &mut connection).unwrap();
+        let count = |sql: &str| -> i64 { connection.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM records"), 5);
+        assert_eq!(
+            count("SELECT COUNT(*) FROM courses WHERE record_id = 'd'"),
+            2
+        );
+        assert_eq!(
+            count("SELECT COUNT(*) FROM authors WHERE record_id = 'c' AND role = 'translator'"),
+            2
+        );
+        assert_eq!(count("SELECT filesize FROM records WHERE id = 'a'"), 1024);
+        let course: String = connection
+            .query_row(
+                "SELECT c.name FROM records r JOIN courses c ON c.record_id = r.id WHERE r.type = 'test' ORDER BY r.id",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(course, "数据结构");
+    }
+}
@@ -0,0 +1,89 @@
+use std::{collections::BTreeMap, path::Path};
+
+use rusoto_s3::S3Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{phash, storage::download_data_json};
+
+pub const FILE_INDEX_NAME: &str = "file-index.json";
+
+/// 本地保存的文件信息索引, 由 upload-metadata 在处理文件时更新, 以文件的 md5 为键
+#[derive(Deserialize, Serialize, Debug, Default)]
+pub struct FileIndex {
+    pub files: BTreeMap<String, FileInfo>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Default, Clone)]
+pub struct FileInfo {
+    /// 第一页渲染结果的 dHash, 十六进制
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub phash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub has_text_layer: Option<bool>,
+    /// 补建全文索引时无法提取文字, 之后不再尝试
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fulltext_failed: Option<bool>,
+}
+
+impl FileIndex {
+    /// 索引文件不存在时返回空索引
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read file index {:?}: {}", path, e))?;
+        Ok(serde_json::from_str(&source)?)
+    }
+
+    /// 从 R2 的数据桶下载, 不存在时返回空索引
+    pub async fn download(s3_client: &S3Client, bucket: &str) -> anyhow::Result<Self> {
+        download_data_json(s3_client, bucket, FILE_INDEX_NAME).await
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn entry(&mut self, md5: &str) -> &mut FileInfo {
+        self.files.entry(md5.to_string()).or_default()
+    }
+
+    /// 找出封面与给定哈希相近的其他文件, 按距离排序
+    pub fn find_similar(&self, md5: &str, hash: u64, max_distance: u32) -> Vec<(&str, u32)> {
+        let mut similar = self
+            .files
+            .iter()
+            .filter(|(other, _)| other.as_str() != md5)
+            .filter_map(|(other, info)| {
+                let other_hash = phash::from_hex(info.phash.as_deref()?)?;
+                let distance = phash::distance(hash, other_hash);
+                (distance <= max_distance).then_some((other.as_str(), distance))
+            })
+            .collect::<Vec<_>>();
+        similar.sort_by_key(|(other, distance)| (*distance, *other));
+        similar
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_similar() {
+        let mut index = FileIndex::default();
+        index.entry("a").phash = Some(phash::to_hex(0b1111));
+        index.entry("b").phash = Some(phash::to_hex(0b0111));
+        index.entry("c").phash = Some(phash::to_hex(u64::MAX));
+        index.entry("d");
+        let index: FileIndex =
+            serde_json::from_str(&serde_json::to_string(&index).unwrap()).unwrap();
+
+        assert_eq!(index.find_similar("a", 0b1111, 2), [("b", 1)]);
+        assert_eq!(index.find_similar("new", 0b1111, 2), [("a", 0), ("b", 1)]);
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashSet;
+
+use crate::{
+    fix::{find_key_separator, split_value},
+    rules::Finding,
+};
+
+/// 元信息文件大小的默认上限, 正常的元信息文件只有几百字节
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 64 * 1024;
+
+/// 在解析 YAML 之前检查文件本身, 给出比 serde 报错更清楚的提示
+pub fn check_file(bytes: &[u8], max_size: u64) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if bytes.len() as u64 > max_size {
+        findings.push(Finding::new(
+            "yaml-file-size",
+            format!("文件过大: {} 字节, 上限为 {} 字节", bytes.len(), max_size),
+        ));
+    }
+    let source = match std::str::from_utf8(bytes) {
+        Ok(source) => source,
+        Err(e) => {
+            findings.push(Finding::new(
+                "yaml-encoding",
+                format!("文件不是有效的UTF-8编码: 第 {} 字节", e.valid_up_to()),
+            ));
+            return findings;
+        }
+    };
+    let source = match source.strip_prefix('\u{FEFF}') {
+        Some(source) => {
+            findings.push(Finding::new("yaml-bom", "文件开头含有BOM, 请去掉"));
+            source
+        }
+        None => source,
+    };
+    if source.contains("\r\n") {
+        findings.push(Finding::new("yaml-crlf", "请使用LF换行, 而不是CRLF"));
+    }
+
+    let mut keys: Vec<(usize, HashSet<String>)> = Vec::new();
+    let mut has_content = false;
+    let mut separators = 0;
+    let mut block_scalar: Option<usize> = None;
+    for (index, line) in source.lines().enumerate() {
+        let line_number = index + 1;
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        if let Some(block_indent) = block_scalar {
+            if line.trim().is_empty() || indent > block_indent {
+                continue;
+            }
+            block_scalar = None;
+        }
+        if line.contains('\t') {
+            findings.push(Finding::new(
+                "yaml-tab",
+                format!("第 {} 行含有制表符, 请使用空格", line_number),
+            ));
+        }
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+        if trimmed == "---" || trimmed.starts_with("--- ") || trimmed == "..." {
+            separators += 1;
+            if has_content || separators > 1 || trimmed == "..." {
+                findings.push(Finding::new(
+                    "yaml-multi-document",
+                    format!("第 {} 行: 一个文件中只能有一个YAML文档", line_number),
+                ));
+            }
+            keys.clear();
+            continue;
+        }
+        has_content = true;
+
+        let mut content = &line[indent..];
+        let mut item = false;
+        while let Some(rest) = content.strip_prefix("- ") {
+            item = true;
+            content = rest.trim_start_matches(' ');
+        }
+        let column = line.len() - content.len();
+        if item {
+            keys.retain(|(key_indent, _)| *key_indent < column);
+        } else {
+            keys.retain(|(key_indent, _)| *key_indent <= column);
+        }
+
+        if let Some((_, value)) = split_value(line) {
+            if value.starts_with('&') || value.starts_with('*') {
+                findings.push(Finding::new(
+                    "yaml-anchor",
+                    format!("第 {} 行: 请不要使用锚点或别名", line_number),
+                ));
+            }
+            if value.starts_with('|') || value.starts_with('>') {
+                block_scalar = Some(indent);
+            }
+        }
+
+        if content.starts_with(['"', '\'', '[', '{']) {
+            continue;
+        }
+        let Some(position) = find_key_separator(content) else {
+            continue;
+        };
+        let key = content[..position].trim().to_string();
+        if key == "<<" {
+            findings.push(Finding::new(
+                "yaml-anchor",
+                format!("第 {} 行: 请不要使用合并键 \"<<\"", line_number),
+            ));
+        }
+        match keys.last_mut() {
+            Some((key_indent, seen)) if *key_indent == column => {
+                if !seen.insert(key.clone()) {
+                    findings.push(
+                        Finding::new(
+                            "yaml-duplicate-key",
+                            format!("第 {} 行: 重复的键 {:?}", line_number, key),
+                        )
+                        .with_key(key.as_str()),
+                    );
+                }
+            }
+            _ => keys.push((column, HashSet::from([key]))),
+        }
+    }
+    findings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rules(source: &[u8]) -> Vec<&'static str> {
+        check_file(source, DEFAULT_MAX_FILE_SIZE)
+            .into_iter()
+            .map(|finding| finding.rule)
+            .collect()
+    }
+
+    #[test]
+    fn test_check_file() {
+        let valid = "# comment\n---\nid: abc\ntype: test\ndata:\n  course:\n    name: a\n  time:\n    name: b\n  content:\n    - 原题\n  college: [a, b]\n";
+        assert!(rules(valid.as_bytes()).is_empty());
+        let doc =
+            "data:\n  course:\n    - type: 本科\n      name: a\n    - type: 本科\n      name: b\n";
+        assert!(rules(doc.as_bytes()).is_empty());
+
+        assert_eq!(rules(b"id: \xff\n"), ["yaml-encoding"]);
+        assert_eq!(rules("\u{FEFF}id: abc\n".as_bytes()), ["yaml-bom"]);
+        assert_eq!(rules(b"id: abc\r\ntype: test\r\n"), ["yaml-crlf"]);
+        assert_eq!(rules(b"data:\n\ttitle: a\n"), ["yaml-tab"]);
+        assert_eq!(rules(b"id: a\n---\nid: b\n"), ["yaml-multi-document"]);
+        assert_eq!(rules(b"a: &x 1\nb: *x\n"), ["yaml-anchor", "yaml-anchor"]);
+        assert_eq!(rules(b"data:\n  <<: {}\n"), ["yaml-anchor"]);
+        assert_eq!(
+            rules(b"data:\n  title: a\n  title: b\nid: a\nid: b\n"),
+            ["yaml-duplicate-key", "yaml-duplicate-key"]
+        );
+        assert_eq!(
+            rules(b"data:\n  course:\n    - name: a\n      name: b\n"),
+            ["yaml-duplicate-key"]
+        );
+        assert_eq!(check_file(b"id: abc\n", 4)[0].rule, "yaml-file-size");
+    }
+}
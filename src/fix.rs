@@ -0,0 +1,352 @@
+use std::collections::HashSet;
+
+/// 对元信息源文件做保留注释和格式的逐行改写, 只处理元信息文件中用到的简单 YAML 写法
+///
+/// 每一行被拆成 `缩进 + 可选的"- " + 可选的"key:" + 值 + 行尾注释`,
+/// 只有值发生变化的行才会被重新生成, 其余内容保持原样
+pub fn map_scalars(source: &str, f: impl Fn(&str) -> String) -> String {
+    let mut output = String::with_capacity(source.len());
+    for line in source.split_inclusive('\n') {
+        let (content, newline) = split_newline(line);
+        match rewrite_line(content, &f) {
+            Some(rewritten) => {
+                output.push_str(&rewritten);
+                output.push_str(newline);
+            }
+            None => output.push_str(line),
+        }
+    }
+    output
+}
+
+fn split_newline(line: &str) -> (&str, &str) {
+    if let Some(content) = line.strip_suffix("\r\n") {
+        (content, "\r\n")
+    } else if let Some(content) = line.strip_suffix('\n') {
+        (content, "\n")
+    } else {
+        (line, "")
+    }
+}
+
+/// 把一行拆成 (值之前的部分, 值及注释部分)
+pub(crate) fn split_value(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim_start();
+    if trimmed.is_empty() || trimmed.starts_with('#') {
+        return None;
+    }
+    let mut offset = line.len() - trimmed.len();
+    let mut rest = trimmed;
+    while let Some(item) = rest.strip_prefix("- ") {
+        offset += 2;
+        rest = item;
+    }
+    if rest == "-" {
+        return None;
+    }
+    if !rest.starts_with(['"', '\'', '[', '{'])
+        && let Some(position) = find_key_separator(rest)
+    {
+        offset += position + 1;
+        rest = &rest[position + 1..];
+    }
+    let value = rest.trim_start();
+    offset += rest.len() - value.len();
+    if value.is_empty() || value.starts_with('#') {
+        return None;
+    }
+    Some((&line[..offset], &line[offset..]))
+}
+
+pub(crate) fn find_key_separator(rest: &str) -> Option<usize> {
+    let bytes = rest.as_bytes();
+    for (index, &byte) in bytes.iter().enumerate() {
+        if byte == b'#' && index > 0 && bytes[index - 1] == b' ' {
+            return None;
+        }
+        if byte == b':' && (index + 1 == bytes.len() || bytes[index + 1] == b' ') {
+            return Some(index);
+        }
+    }
+    None
+}
+
+fn rewrite_line(line: &str, f: &impl Fn(&str) -> String) -> Option<String> {
+    let (prefix, rest) = split_value(line)?;
+    let (value, trailing) = split_scalar(rest)?;
+    let rewritten = match value.as_bytes()[0] {
+        b'[' => rewrite_flow_sequence(value, f)?,
+        b'|' | b'>' | b'&' | b'*' | b'{' | b'!' => return None,
+        _ => rewrite_scalar(value, f)?,
+    };
+    if rewritten == value {
+        return None;
+    }
+    Some(format!("{prefix}{rewritten}{trailing}"))
+}
+
+/// 把值和行尾注释分开
+fn split_scalar(rest: &str) -> Option<(&str, &str)> {
+    let end = match rest.as_bytes()[0] {
+        b'"' => closing_double_quote(rest)? + 1,
+        b'\'' => closing_single_quote(rest)? + 1,
+        b'[' => closing_bracket(rest)? + 1,
+        _ => rest
+            .find(" #")
+            .map(|position| rest[..position].trim_end().len())
+            .unwrap_or(rest.trim_end().len()),
+    };
+    Some((&rest[..end], &rest[end..]))
+}
+
+fn closing_double_quote(value: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (index, c) in value.char_indices().skip(1) {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return Some(index),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn closing_single_quote(value: &str) -> Option<usize> {
+    let bytes = value.as_bytes();
+    let mut index = 1;
+    while index < bytes.len() {
+        if bytes[index] == b'\'' {
+            if bytes.get(index + 1) == Some(&b'\'') {
+                index += 2;
+                continue;
+            }
+            return Some(index);
+        }
+        index += 1;
+    }
+    None
+}
+
+fn closing_bracket(value: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in value.char_indices().skip(1) {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ']') => return Some(index),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// 拆分单行的 flow sequence, 例如 `[原题, 答案]`
+pub(crate) fn flow_sequence_items(value: &str) -> Option<Vec<&str>> {
+    let inner = value.strip_prefix('[')?.strip_suffix(']')?;
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (index, c) in inner.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '[' | '{') => return None,
+            (None, ',') => {
+                items.push(inner[start..index].trim());
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    let last = inner[start..].trim();
+    if !last.is_empty() || !items.is_empty() {
+        items.push(last);
+    }
+    Some(items)
+}
+
+fn rewrite_flow_sequence(value: &str, f: &impl Fn(&str) -> String) -> Option<String> {
+    let items = flow_sequence_items(value)?;
+    let rewritten = items
+        .iter()
+        .map(|item| rewrite_scalar(item, f).unwrap_or_else(|| item.to_string()))
+        .collect::<Vec<_>>();
+    Some(format!("[{}]", rewritten.join(", ")))
+}
+
+/// 把 YAML 标量解析为字符串, 不支持的写法返回 None
+pub(crate) fn parse_scalar(value: &str) -> Option<String> {
+    if let Some(inner) = value.strip_prefix('"') {
+        let inner = inner.strip_suffix('"')?;
+        let mut output = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                output.push(c);
+                continue;
+            }
+            match chars.next()? {
+                '"' => output.push('"'),
+                '\\' => output.push('\\'),
+                '/' => output.push('/'),
+                't' => output.push('\t'),
+                'n' => output.push('\n'),
+                _ => return None,
+            }
+        }
+        Some(output)
+    } else if let Some(inner) = value.strip_prefix('\'') {
+        Some(inner.strip_suffix('\'')?.replace("''", "'"))
+    } else {
+        Some(value.to_string())
+    }
+}
+
+fn rewrite_scalar(value: &str, f: &impl Fn(&str) -> String) -> Option<String> {
+    let parsed = parse_scalar(value)?;
+    let mapped = f(&parsed);
+    if mapped == parsed {
+        return Some(value.to_string());
+    }
+    Some(if value.starts_with('\'') {
+        format!("'{}'", mapped.replace('\'', "''"))
+    } else if value.starts_with('"') || needs_quotes(&mapped) {
+        format!("\"{}\"", mapped.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        mapped
+    })
+}
+
+fn needs_quotes(value: &str) -> bool {
+    value.is_empty()
+        || value.starts_with([
+            '-', '?', ':', ',', '[', ']', '{', '}', '#', '&', '*', '!', '|', '>', '\'', '"', '%',
+            '@', '`', ' ',
+        ])
+        || value.ends_with(' ')
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.contains(['\t', '\n'])
+}
+
+/// 去掉序列中重复的元素, 相同的判断依据为 `key` 的返回值, 保留第一次出现的元素
+///
+/// block sequence 的每个元素包括 `- ` 所在行以及其后缩进更深的行,
+/// 多行元素 (例如 Doc 的 course) 按去掉缩进后的各行内容比较
+pub fn dedup_sequences(source: &str, key: impl Fn(&str) -> String) -> String {
+    let lines = source.split_inclusive('\n').collect::<Vec<_>>();
+    let mut output = String::with_capacity(source.len());
+    let mut sequences: Vec<(usize, HashSet<String>)> = Vec::new();
+    let mut index = 0;
+    while index < lines.len() {
+        let line = lines[index];
+        let content = split_newline(line).0;
+        let trimmed = content.trim_start();
+        let indent = content.len() - trimmed.len();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            output.push_str(line);
+            index += 1;
+            continue;
+        }
+        let is_item = trimmed.starts_with("- ") || trimmed == "-";
+        sequences.retain(|(column, _)| *column < indent || (*column == indent && is_item));
+        if !is_item {
+            match dedup_flow_line(content, &key) {
+                Some(rewritten) => {
+                    output.push_str(&rewritten);
+                    output.push_str(split_newline(line).1);
+                }
+                None => output.push_str(line),
+            }
+            index += 1;
+            continue;
+        }
+
+        let mut end = index + 1;
+        while end < lines.len() {
+            let next = split_newline(lines[end]).0;
+            let next_trimmed = next.trim_start();
+            if next_trimmed.is_empty() || next.len() - next_trimmed.len() <= indent {
+                break;
+            }
+            end += 1;
+        }
+        let block = &lines[index..end];
+        if sequences.last().is_none_or(|(column, _)| *column != indent) {
+            sequences.push((indent, HashSet::new()));
+        }
+        let seen = &mut sequences.last_mut().unwrap().1;
+        let duplicated = item_key(block, &key).is_some_and(|item| !seen.insert(item));
+        if !duplicated {
+            block.iter().for_each(|line| output.push_str(line));
+        }
+        index = end;
+    }
+    output
+}
+
+fn item_key(block: &[&str], key: &impl Fn(&str) -> String) -> Option<String> {
+    let first = split_newline(block[0]).0.trim_start();
+    let first = first.strip_prefix('-')?.trim_start();
+    if block.len() == 1 {
+        let (value, _) = split_scalar(first)?;
+        if value.starts_with(['[', '{', '&', '*', '|', '>', '!']) {
+            return None;
+        }
+        return Some(key(&parse_scalar(value)?));
+    }
+    let mut contents = block[1..]
+        .iter()
+        .map(|line| split_newline(line).0.trim().to_string())
+        .collect::<Vec<_>>();
+    contents.push(first.trim_end().to_string());
+    contents.sort();
+    Some(contents.join("\n"))
+}
+
+fn dedup_flow_line(line: &str, key: &impl Fn(&str) -> String) -> Option<String> {
+    let (prefix, rest) = split_value(line)?;
+    if !rest.starts_with('[') {
+        return None;
+    }
+    let (value, trailing) = split_scalar(rest)?;
+    let items = flow_sequence_items(value)?;
+    let mut seen = HashSet::new();
+    let mut kept = Vec::new();
+    for item in &items {
+        if seen.insert(key(&parse_scalar(item)?)) {
+            kept.push(*item);
+        }
+    }
+    if kept.len() == items.len() {
+        return None;
+    }
+    Some(format!("{prefix}[{}]{trailing}", kept.join(", ")))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_map_scalars() {
+        let source = "# byrdocs-check: allow time\nid: abc\ndata:\n  title: \"数据结构 \" # 注释\n  authors:\n    - 张三 \n  content: [原题, '答案 ']\n";
+        let fixed = map_scalars(source, |value| value.trim().to_string());
+        assert_eq!(
+            fixed,
+            "# byrdocs-check: allow time\nid: abc\ndata:\n  title: \"数据结构\" # 注释\n  authors:\n    - 张三 \n  content: [原题, '答案']\n"
+        );
+        let fixed = map_scalars(source, |value| value.replace("张三", "李四"));
+        assert!(fixed.contains("    - 李四 \n"));
+        assert_eq!(map_scalars(source, |value| value.to_string()), source);
+    }
+
+    #[test]
+    fn test_dedup_sequences() {
+        let source = "data:\n  content: [原题, 答案, '原题']\n  authors:\n    - 张三\n    - \"张三\"\n    - 李四\n  course:\n    - type: 本科\n      name: a\n    - name: a\n      type: 本科\n  college:\n    - 张三\n";
+        assert_eq!(
+            dedup_sequences(source, |value| value.to_string()),
+            "data:\n  content: [原题, 答案]\n  authors:\n    - 张三\n    - 李四\n  course:\n    - type: 本科\n      name: a\n  college:\n    - 张三\n"
+        );
+    }
+}
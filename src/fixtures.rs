@@ -0,0 +1,23 @@
+//! 各模块测试共用的元信息样例
+
+use crate::metadata::*;
+
+/// 覆盖三种类型的一组元信息: 两份试题 (a, b), 两本书 (c, e) 和一份资料 (d).
+/// a 和 c 带有文件大小, e 的字段需要转义且缺少出版信息
+pub fn records() -> Vec<MetaData> {
+    let mut records = [
+        "id: a\nurl: https://byrdocs.org/files/a.pdf\ntype: test\ndata:\n  college: [计算机学院]\n  course: {type: 本科, name: 数据结构}\n  time: {start: '2022', end: '2023', stage: 期末}\n  filetype: pdf\n  content: [原题, 答案]\n",
+        "id: b\nurl: https://byrdocs.org/files/b.zip\ntype: test\ndata:\n  course: {name: 高等数学A}\n  time: {start: '2023', end: '2024', semester: First, stage: 期中}\n  filetype: zip\n  content: [原题]\n",
+        "id: c\nurl: https://byrdocs.org/files/c.pdf\ntype: book\ndata:\n  title: C++ Primer 中文版\n  authors: [Stanley B. Lippman, Josée Lajoie]\n  translators: [王刚, 杨巨峰]\n  edition: '5'\n  publish_year: '2013'\n  publisher: 电子工业出版社\n  isbn: [978-7-121-15535-2]\n  filetype: pdf\n",
+        "id: d\nurl: https://byrdocs.org/files/d.pdf\ntype: doc\ndata:\n  title: 复习资料\n  course: [{type: 本科, name: 高等数学A}, {name: 数据结构}]\n  filetype: pdf\n  content: [知识点]\n",
+        "id: e\nurl: https://byrdocs.org/files/e.pdf\ntype: book\ndata:\n  title: 50% & more_{x}\n  authors: []\n  isbn: []\n  filetype: pdf\n",
+    ]
+    .map(|source| serde_yaml::from_str::<MetaData>(source).unwrap());
+    if let Data::Test(test) = &mut records[0].data {
+        test.filesize = Some(1024);
+    }
+    if let Data::Book(book) = &mut records[2].data {
+        book.filesize = Some(3 * 1024 * 1024);
+    }
+    records.into()
+}
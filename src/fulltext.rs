@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::hygiene::is_cjk;
+
+pub const FULLTEXT_INDEX_NAME: &str = "fulltext.json";
+
+/// 把文本切分为检索词: 连续的汉字切成相邻两字组成的词 (只有一个字时保留单字),
+/// 字母和数字按单词切分并转为小写
+///
+/// 二元切分不需要词典, 检索时对查询做同样的切分即可匹配任意长度的中文词
+pub fn tokenize(text: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut cjk = Vec::new();
+    let mut word = String::new();
+    let flush_cjk = |cjk: &mut Vec<char>, tokens: &mut Vec<String>| {
+        match cjk.len() {
+            0 => (),
+            1 => tokens.push(cjk[0].to_string()),
+            _ => tokens.extend(cjk.windows(2).map(|pair| pair.iter().collect())),
+        }
+        cjk.clear();
+    };
+    let flush_word = |word: &mut String, tokens: &mut Vec<String>| {
+        if !word.is_empty() {
+            tokens.push(std::mem::take(word));
+        }
+    };
+    for c in text.chars() {
+        if is_cjk(c) {
+            flush_word(&mut word, &mut tokens);
+            cjk.push(c);
+        } else if c.is_alphanumeric() {
+            flush_cjk(&mut cjk, &mut tokens);
+            word.extend(c.to_lowercase());
+        } else {
+            flush_cjk(&mut cjk, &mut tokens);
+            flush_word(&mut word, &mut tokens);
+        }
+    }
+    flush_cjk(&mut cjk, &mut tokens);
+    flush_word(&mut word, &mut tokens);
+    tokens
+}
+
+/// 以文件 md5 为文档的倒排索引, 每个词对应 `[文档序号, 出现次数]` 的列表
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+pub struct FullTextIndex {
+    pub documents: Vec<String>,
+    pub terms: BTreeMap<String, Vec<(u32, u32)>>,
+}
+
+impl FullTextIndex {
+    pub fn contains(&self, md5: &str) -> bool {
+        self.documents.iter().any(|document| document == md5)
+    }
+
+    /// 添加文档, 已经存在时替换原有内容; 没有任何检索词的文档不会加入索引
+    pub fn add(&mut self, md5: &str, text: &str) {
+        self.remove(md5);
+        let mut counts = BTreeMap::new();
+        for token in tokenize(text) {
+            *counts.entry(token).or_insert(0) += 1;
+        }
+        if counts.is_empty() {
+            return;
+        }
+        let document = self.documents.len() as u32;
+        self.documents.push(md5.to_string());
+        for (term, count) in counts {
+            self.terms.entry(term).or_default().push((document, count));
+        }
+    }
+
+    pub fn remove(&mut self, md5: &str) {
+        if let Some(document) = self.documents.iter().position(|other| other == md5) {
+            self.remove_documents(&[document as u32]);
+        }
+    }
+
+    /// 只保留满足条件的文档, 用于去掉已经删除的元信息对应的文件
+    pub fn retain(&mut self, f: impl Fn(&str) -> bool) {
+        let removed = self
+            .documents
+            .iter()
+            .enumerate()
+            .filter(|(_, md5)| !f(md5))
+            .map(|(document, _)| document as u32)
+            .collect::<Vec<_>>();
+        if !removed.is_empty() {
+            self.remove_documents(&removed);
+        }
+    }
+
+    /// `removed` 需要按升序排列, 删除后其余文档的序号依次前移
+    fn remove_documents(&mut self, removed: &[u32]) {
+        let shift = |document: u32| document - removed.partition_point(|&r| r < document) as u32;
+        for postings in self.terms.values_mut() {
+            postings.retain(|(document, _)| removed.binary_search(document).is_err());
+            for (document, _) in postings.iter_mut() {
+                *document = shift(*document);
+            }
+        }
+        self.terms.retain(|_, postings| !postings.is_empty());
+        for &document in removed.iter().rev() {
+            self.documents.remove(document as usize);
+        }
+    }
+
+    /// 查找包含查询中所有检索词的文档, 按出现次数之和降序排列
+    pub fn search(&self, query: &str) -> Vec<(&str, u32)> {
+        let tokens = tokenize(query);
+        let mut scores: Option<BTreeMap<u32, u32>> = None;
+        for token in &tokens {
+            let postings = self.terms.get(token).map(Vec::as_slice).unwrap_or_default();
+            scores = Some(match scores {
+                None => postings.iter().copied().collect(),
+                Some(scores) => postings
+                    .iter()
+                    .filter_map(|(document, count)| {
+                        Some((*document, scores.get(document)? + count))
+                    })
+                    .collect(),
+            });
+        }
+        let mut results = scores
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(document, score)| (self.documents[document as usize].as_str(), score))
+            .collect::<Vec<_>>();
+        results.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        results
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("数据结构 Final-Exam 2023年"),
+            ["数据", "据结", "结构", "final", "exam", "2023", "年"]
+        );
+    }
+
+    #[test]
+    fn test_full_text_index() {
+        let mut index = FullTextIndex::default();
+        index.add("a", "数据结构期末考试 数据结构");
+        index.add("b", "概率论与数理统计期末考试");
+        index.add("c", "图片扫描件, 没有文字层");
+        index.add("d", "  ");
+        assert!(!index.contains("d"));
+        assert_eq!(index.search("期末考试"), [("a", 3), ("b", 3)]);
+        assert_eq!(index.search("数据结构"), [("a", 6)]);
+        assert!(index.search("线性代数").is_empty());
+
+        index.retain(|md5| md5 != "a");
+        index.add("c", "线性代数");
+        assert_eq!(index.documents, ["b", "c"]);
+        assert_eq!(index.search("期末"), [("b", 1)]);
+        assert_eq!(index.search("线性代数"), [("c", 3)]);
+
+        let mut expected = FullTextIndex::default();
+        expected.add("b", "概率论与数理统计期末考试");
+        expected.add("c", "线性代数");
+        assert_eq!(index, expected);
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{metadata::*, rules::Finding};
+
+/// 常见的繁体字及其对应的简体字, 只收录在简体中文中不会单独使用的字
+const TRADITIONAL: &str = "學学習习數数據据結结構构與与計计機机電电網网絡络應应濟济論论體体設设際际題题對对開开關关線线績绩級级課课語语讀读書书寫写說说話话認认識识記记試试驗验義义務务圖图導导歷历經经會会議议點点時时間间問问實实現现發发處处統统變变換换觀观視视聽听覺觉錄录類类參参專专業业員员師师範范環环氣气溫温熱热離离幾几難难復复複复雜杂條条單单雙双號号碼码訊讯軟软資资產产權权國国東东車车輛辆鐵铁區区醫医藥药療疗衛卫護护廣广報报紙纸傳传遠远達达過过還还這这麼么們们個个來来為为當当從从後后裡里邊边兩两萬万億亿無无愛爱樂乐聲声陽阳陰阴極极積积階阶層层標标準准確确態态勢势動动勞劳農农藝艺術术獨独陳陈張张劉刘楊杨黃黄趙赵吳吴孫孙馬马鄭郑謝谢鄧邓馮冯許许蘇苏蔣蒋韋韦錢钱銀银財财貨货貿贸價价費费額额稅税償偿債债險险證证幣币匯汇總总帳账組组織织紀纪練练測测評评診诊斷断續续補补擴扩壓压縮缩檔档頁页頻频觸触腦脑鍵键盤盘顯显螢萤擊击藍蓝綠绿紅红顏颜鏈链節节運运輸输簡简轉转載载場场廠厂礦矿彈弹戰战爭争軍军隊队舊旧濕湿漢汉詞词彙汇譯译註注釋释擬拟規规劃划讓让進进選选擇择適适優优質质靜静錯错誤误";
+
+fn to_simplified(c: char) -> Option<char> {
+    let mut chars = TRADITIONAL.chars();
+    while let (Some(traditional), Some(simplified)) = (chars.next(), chars.next()) {
+        if traditional == c {
+            return Some(simplified);
+        }
+    }
+    None
+}
+
+fn is_zero_width(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' | '\u{00AD}'
+    )
+}
+
+pub(crate) fn is_cjk(c: char) -> bool {
+    matches!(c, '\u{4E00}'..='\u{9FFF}' | '\u{3400}'..='\u{4DBF}' | '\u{F900}'..='\u{FAFF}')
+}
+
+fn remove_zero_width(value: &str) -> String {
+    value.chars().filter(|c| !is_zero_width(*c)).collect()
+}
+
+/// 去掉首尾空白, 全角空格和不换行空格视为普通空格, 连续空白合并为一个空格
+fn normalize_whitespace(value: &str) -> String {
+    value
+        .split(|c: char| c.is_whitespace() || c == '\u{3000}')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn simplify(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| to_simplified(c).unwrap_or(c))
+        .collect()
+}
+
+/// 含有中文时使用全角括号, 否则使用半角括号
+fn normalize_parentheses(value: &str) -> String {
+    if value.chars().any(is_cjk) {
+        value.replace('(', "（").replace(')', "）")
+    } else {
+        value.replace('（', "(").replace('）', ")")
+    }
+}
+
+/// 中文与英文、数字之间的空格, 返回 (有空格的边界数, 没有空格的边界数)
+fn cjk_boundaries(value: &str) -> (usize, usize) {
+    let chars = value.chars().collect::<Vec<_>>();
+    let mut spaced = 0;
+    let mut unspaced = 0;
+    for (index, pair) in chars.windows(2).enumerate() {
+        if is_boundary(pair[0], pair[1]) {
+            unspaced += 1;
+        } else if pair[1] == ' '
+            && let Some(&next) = chars.get(index + 2)
+            && is_boundary(pair[0], next)
+        {
+            spaced += 1;
+        }
+    }
+    (spaced, unspaced)
+}
+
+fn is_boundary(a: char, b: char) -> bool {
+    (is_cjk(a) && b.is_ascii_alphanumeric()) || (a.is_ascii_alphanumeric() && is_cjk(b))
+}
+
+/// 同一字段中有的边界有空格有的没有时, 统一去掉中英文之间的空格
+fn normalize_cjk_spacing(value: &str) -> String {
+    let (spaced, unspaced) = cjk_boundaries(value);
+    if spaced == 0 || unspaced == 0 {
+        return value.to_string();
+    }
+    let chars = value.chars().collect::<Vec<_>>();
+    let mut output = String::with_capacity(value.len());
+    for (index, &c) in chars.iter().enumerate() {
+        if c == ' '
+            && index > 0
+            && let Some(&next) = chars.get(index + 1)
+            && is_boundary(chars[index - 1], next)
+        {
+            continue;
+        }
+        output.push(c);
+    }
+    output
+}
+
+type Step = (&'static str, &'static str, fn(&str) -> String);
+
+/// 按顺序执行的规范化步骤, 每一步对应一条规则
+const STEPS: &[Step] = &[
+    ("text-zero-width", "含有零宽字符", remove_zero_width),
+    ("text-whitespace", "含有多余的空白", normalize_whitespace),
+    ("text-traditional", "含有繁体字", simplify),
+    (
+        "text-parenthesis",
+        "括号全角半角不统一",
+        normalize_parentheses,
+    ),
+    (
+        "text-cjk-spacing",
+        "中英文之间的空格不一致",
+        normalize_cjk_spacing,
+    ),
+];
+
+/// 将字段转换为规范形式, 只执行 `enabled` 允许的规则
+pub fn normalize_text(value: &str, enabled: impl Fn(&str) -> bool) -> String {
+    STEPS
+        .iter()
+        .filter(|(rule, _, _)| enabled(rule))
+        .fold(value.to_string(), |value, (_, _, step)| step(&value))
+}
+
+pub fn lint_text(field: &str, value: &str) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let mut current = value.to_string();
+    for (rule, message, step) in STEPS {
+        let next = step(&current);
+        if next != current {
+            findings.push(
+                Finding::new(rule, format!("{}: {}: {:?}", field, message, value)).with_key(field),
+            );
+            current = next;
+        }
+    }
+    findings
+}
+
+/// 遍历元信息中所有的字符串字段, 回调参数为字段路径和字段值
+pub fn visit_strings(data: &Data, f: &mut impl FnMut(&str, &str)) {
+    fn course(prefix: &str, course: &Course, f: &mut impl FnMut(&str, &str)) {
+        if let Some(type_) = &course.type_ {
+            f(&format!("{prefix}.type"), type_);
+        }
+        f(&format!("{prefix}.name"), &course.name);
+    }
+    fn list(prefix: &str, values: &[String], f: &mut impl FnMut(&str, &str)) {
+        for (index, value) in values.iter().enumerate() {
+            f(&format!("{prefix}[{index}]"), value);
+        }
+    }
+    fn optional(field: &str, value: &Option<String>, f: &mut impl FnMut(&str, &str)) {
+        if let Some(value) = value {
+            f(field, value);
+        }
+    }
+    match data {
+        Data::Test(test) => {
+            list("college", test.college.as_deref().unwrap_or_default(), f);
+            course("course", &test.course, f);
+            f("time.start", &test.time.start);
+            f("time.end", &test.time.end);
+            optional("time.semester", &test.time.semester, f);
+            optional("time.stage", &test.time.stage, f);
+            f("filetype", &test.filetype);
+            list("content", &test.content, f);
+        }
+        Data::Book(book) => {
+            f("title", &book.title);
+            list("authors", &book.authors, f);
+            list(
+                "translators",
+                book.translators.as_deref().unwrap_or_default(),
+                f,
+            );
+            optional("edition", &book.edition, f);
+            optional("publish_year", &book.publish_year, f);
+            optional("publisher", &book.publisher, f);
+            list("isbn", &book.isbn, f);
+            f("filetype", &book.filetype);
+        }
+        Data::Doc(doc) => {
+            f("title", &doc.title);
+            f("filetype", &doc.filetype);
+            for (index, item) in doc.course.iter().enumerate() {
+                course(&format!("course[{index}]"), item, f);
+            }
+            list("content", &doc.content, f);
+        }
+    }
+}
+
+pub fn check_text(data: &Data) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    visit_strings(data, &mut |field, value| {
+        findings.extend(lint_text(field, value));
+    });
+    findings
+}
+
+/// 在源文件上自动修复文本问题, 没有需要修复的内容时返回 None
+pub fn fix_text(source: &str, enabled: impl Fn(&str) -> bool) -> Option<String> {
+    let fixed = crate::fix::map_scalars(source, |value| normalize_text(value, &enabled));
+    (fixed != source).then_some(fixed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn rules(field: &str, value: &str) -> Vec<&'static str> {
+        lint_text(field, value)
+            .into_iter()
+            .map(|finding| finding.rule)
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_text() {
+        assert!(rules("title", "数据结构（C语言版）").is_empty());
+        assert!(rules("title", "C++ Primer (5th Edition)").is_empty());
+        assert_eq!(rules("title", "数据结构\u{200B}"), ["text-zero-width"]);
+        assert_eq!(rules("title", " 数据结构  基础"), ["text-whitespace"]);
+        assert_eq!(rules("title", "數據結構"), ["text-traditional"]);
+        assert_eq!(rules("title", "大学物理(上)"), ["text-parenthesis"]);
+        assert_eq!(rules("title", "Primer（5th）"), ["text-parenthesis"]);
+        assert_eq!(rules("title", "高等数学A 上册"), ["text-cjk-spacing"]);
+        assert!(rules("title", "Python 编程 从入门到实践").is_empty());
+    }
+
+    #[test]
+    fn test_normalize_text() {
+        let all = |_: &str| true;
+        assert_eq!(
+            normalize_text(" 數據結構(C語言版)\u{3000}", all),
+            "数据结构（C语言版）"
+        );
+        assert_eq!(normalize_text("Primer（5th）", all), "Primer(5th)");
+        assert_eq!(normalize_text("高等数学A 上册", all), "高等数学A上册");
+        assert_eq!(
+            normalize_text("大学物理(上) ", |rule| rule != "text-parenthesis"),
+            "大学物理(上)"
+        );
+        for value in ["数据结构（C语言版）", "Python 编程", "2023"] {
+            assert_eq!(normalize_text(value, all), value);
+        }
+    }
+
+    #[test]
+    fn test_fix_text() {
+        let source = "id: abc\ndata:\n  title: 大学物理(上)\n  authors:\n    - \"张三 \"\n";
+        assert_eq!(
+            fix_text(source, |_| true).unwrap(),
+            "id: abc\ndata:\n  title: 大学物理（上）\n  authors:\n    - \"张三\"\n"
+        );
+        assert!(fix_text("id: abc\n", |_| true).is_none());
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::Read,
+    path::Path,
+};
+
+use crate::{
+    corpus::Corpus,
+    dedup::{check_duplicate_values, find_duplicate_tests},
+    hygiene::check_text,
+    metadata::*,
+    rules::Finding,
+    scaffold::{Fields, detect_filetype, draft},
+    validate::{check_data, check_duplicate_isbn},
+};
+
+/// CSV 中的一行, 列名与元信息中的字段名一致, 多个值用分号分隔
+#[derive(serde::Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+struct Row {
+    /// 文件的 md5, 或者相对于 CSV 文件所在目录的本地路径
+    file: String,
+    #[serde(rename = "type")]
+    type_: String,
+    filetype: Option<String>,
+    title: Option<String>,
+    authors: Option<String>,
+    translators: Option<String>,
+    edition: Option<String>,
+    publish_year: Option<String>,
+    publisher: Option<String>,
+    isbn: Option<String>,
+    course: Option<String>,
+    course_type: Option<String>,
+    college: Option<String>,
+    start: Option<String>,
+    end: Option<String>,
+    semester: Option<String>,
+    stage: Option<String>,
+    content: Option<String>,
+}
+
+/// 一行的导入结果, 无法生成元信息时 `metadata` 为空
+#[derive(Debug)]
+pub struct ImportedRow {
+    /// CSV 中的行号, 从表头所在的第 1 行开始计
+    pub line: u64,
+    pub metadata: Option<MetaData>,
+    pub findings: Vec<Finding>,
+}
+
+fn split_list(value: Option<String>) -> Vec<String> {
+    value
+        .unwrap_or_default()
+        .split([';', '；'])
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn is_md5(value: &str) -> bool {
+    value.len() == 32 && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// 由 `file` 列得到 md5 和文件类型: 填写 md5 时需要同时填写 filetype, 填写路径时从文件中计算
+fn resolve_file(row: &Row, base_dir: &Path) -> Result<(String, String), String> {
+    let file = row.file.trim();
+    if is_md5(file) {
+        let filetype = non_empty(row.filetype.clone())
+            .ok_or_else(|| "填写md5时需要同时填写filetype".to_string())?;
+        return Ok((file.to_ascii_lowercase(), filetype));
+    }
+    let path = base_dir.join(file);
+    let bytes = std::fs::read(&path).map_err(|e| format!("无法读取文件 {:?}: {}", path, e))?;
+    let filetype =
+        detect_filetype(&bytes).ok_or_else(|| format!("{:?} 既不是PDF也不是ZIP文件", path))?;
+    Ok((format!("{:x}", md5::compute(&bytes)), filetype.to_string()))
+}
+
+fn row_metadata(row: Row, base_dir: &Path, site_url: &str) -> Result<MetaData, String> {
+    let type_ = row.type_.parse::<Type>()?;
+    let (md5, filetype) = resolve_file(&row, base_dir)?;
+    let fields = Fields {
+        title: non_empty(row.title),
+        authors: split_list(row.authors),
+        translators: split_list(row.translators),
+        edition: non_empty(row.edition),
+        publish_year: non_empty(row.publish_year),
+        publisher: non_empty(row.publisher),
+        isbns: split_list(row.isbn),
+        courses: split_list(row.course),
+        course_type: non_empty(row.course_type),
+        colleges: split_list(row.college),
+        start: non_empty(row.start),
+        end: non_empty(row.end),
+        semester: non_empty(row.semester),
+        stage: non_empty(row.stage),
+        content: split_list(row.content),
+    };
+    Ok(draft(type_, &md5, &filetype, site_url, fields))
+}
+
+/// 与目录中已有的书籍 isbn 重复, 同一 id 的文件会被覆盖, 不算重复
+fn check_existing_isbn(book: &Book, md5: &str, corpus: &Corpus) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for isbn in &book.isbn {
+        for existing in corpus.find_isbn(isbn) {
+            if existing.id != md5 {
+                findings.push(
+                    Finding::new(
+                        "duplicate-isbn",
+                        format!("重复的isbn. md5: {} {}", md5, existing.id),
+                    )
+                    .with_key(isbn.as_str()),
+                );
+            }
+        }
+    }
+    findings
+}
+
+/// 读取 CSV 并为每一行生成元信息, 使用与 check-format 相同的检查,
+/// 并在所有行之间以及与 `corpus` 中已有的文件之间检查重复的 isbn 和试卷
+pub fn import_csv(
+    reader: impl Read,
+    base_dir: &Path,
+    site_url: &str,
+    corpus: &Corpus,
+) -> Vec<ImportedRow> {
+    let mut rows = Vec::new();
+    let mut isbns = HashMap::new();
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers().cloned().unwrap_or_default();
+    for record in reader.records() {
+        let (line, result) = match record {
+            Ok(record) => (
+                record.position().map(|position| position.line()),
+                record
+                    .deserialize::<Row>(Some(&headers))
+                    .map_err(|e| format!("无法解析: {}", e))
+                    .and_then(|row| row_metadata(row, base_dir, site_url)),
+            ),
+            Err(e) => (
+                e.position().map(|position| position.line()),
+                Err(format!("无法解析: {}", e)),
+            ),
+        };
+        let line = line.unwrap_or_default();
+        let mut findings = Vec::new();
+        let metadata = match result {
+            Ok(metadata) => {
+                findings.extend(check_data(&metadata.data));
+                findings.extend(check_text(&metadata.data));
+                findings.extend(check_duplicate_values(&metadata.data));
+                if let Data::Book(book) = &metadata.data {
+                    findings.extend(check_duplicate_isbn(book, &metadata.id, &mut isbns));
+                    findings.extend(check_existing_isbn(book, &metadata.id, corpus));
+                }
+                Some(metadata)
+            }
+            Err(message) => {
+                findings.push(Finding::new("parse-error", message));
+                None
+            }
+        };
+        rows.push(ImportedRow {
+            line,
+            metadata,
+            findings,
+        });
+    }
+
+    let mut tests = rows
+        .iter()
+        .filter_map(|row| match &row.metadata {
+            Some(MetaData {
+                id,
+                data: Data::Test(test),
+                ..
+            }) => Some((id.as_str(), test)),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    // 已有文件中同一课程的试卷, 会被本次导入覆盖的除外
+    let imported = tests.iter().map(|(id, _)| *id).collect::<HashSet<_>>();
+    let courses = tests
+        .iter()
+        .map(|(_, test)| test.course.name.trim())
+        .collect::<HashSet<_>>();
+    for course in courses {
+        for existing in corpus.find_course(course) {
+            if let Data::Test(test) = &existing.data
+                && !imported.contains(existing.id.as_str())
+            {
+                tests.push((existing.id.as_str(), test));
+            }
+        }
+    }
+    let groups = find_duplicate_tests(tests)
+        .into_iter()
+        .map(|group| group.into_iter().map(str::to_string).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    for row in &mut rows {
+        let Some(metadata) = &row.metadata else {
+            continue;
+        };
+        if let Some(group) = groups.iter().find(|group| group.contains(&metadata.id)) {
+            row.findings.push(Finding::new(
+                "duplicate-test",
+                format!("可能与其他试卷重复: {}", group.join(", ")),
+            ));
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_import_csv() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.pdf"), b"%PDF-1.4 a").unwrap();
+        let csv = "\
+file,type,filetype,course,course_type,start,end,stage,content,title,authors,isbn
+a.pdf,test,,数据结构,本科,2023,2024,期末,原题;答案,,,
+0123456789ABCDEF0123456789abcdef,test,zip,数据结构,本科,2023,2024,期末,答案;原题,,,
+0123456789abcdef0123456789abcdee,book,pdf,,,,,,,数据结构,严蔚敏;吴伟民,978-7-111-40772-0
+missing.pdf,test,,数据结构,本科,2023,2024,期末,原题,,,
+11111111111111111111111111111111,exam,pdf,,,,,,,,,
+";
+        let rows = import_csv(
+            csv.as_bytes(),
+            dir.path(),
+            "https://byrdocs.org",
+            &Corpus::default(),
+        );
+        let summary = rows
+            .iter()
+            .map(|row| {
+                (
+                    row.line,
+                    row.metadata.is_some(),
+                    row.findings
+                        .iter()
+                        .map(|finding| finding.rule)
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            summary,
+            [
+                (2, true, vec!["duplicate-test"]),
+                (3, true, vec!["duplicate-test"]),
+                (4, true, vec![]),
+                (5, false, vec!["parse-error"]),
+                (6, false, vec!["parse-error"]),
+            ]
+        );
+        let first = rows[0].metadata.as_ref().unwrap();
+        assert_eq!(first.id, format!("{:x}", md5::compute(b"%PDF-1.4 a")));
+        assert_eq!(
+            rows[1].metadata.as_ref().unwrap().id,
+            "0123456789abcdef0123456789abcdef"
+        );
+        assert!(
+            rows[1]
+                .metadata
+                .as_ref()
+                .unwrap()
+                .url
+                .ends_with("/files/0123456789abcdef0123456789abcdef.zip")
+        );
+
+        let rows = import_csv(
+            "file,typo\na,b\n".as_bytes(),
+            dir.path(),
+            "",
+            &Corpus::default(),
+        );
+        assert_eq!(rows[0].findings[0].rule, "parse-error");
+    }
+
+    #[test]
+    fn test_import_csv_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("b.yml"),
+            "id: b\nurl: u\ntype: book\ndata:\n  title: t\n  authors: [a]\n  isbn: [978-7-111-40772-0]\n  filetype: pdf\n",
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("t.yml"),
+            "id: t\nurl: u\ntype: test\ndata:\n  course: {name: 数据结构, type: 本科}\n  time: {start: '2023', end: '2024', stage: 期末}\n  filetype: pdf\n  content: [原题]\n",
+        )
+        .unwrap();
+        let corpus = Corpus::load(dir.path(), crate::corpus::Layout::Flat).unwrap();
+        let csv = "\
+file,type,filetype,course,course_type,start,end,stage,content,title,authors,isbn
+00000000000000000000000000000001,test,pdf,数据结构,本科,2023,2024,期末,原题,,,
+00000000000000000000000000000002,book,pdf,,,,,,,数据结构,严蔚敏,9787111407720
+00000000000000000000000000000003,test,pdf,数据结构,本科,2023,2024,期中,原题,,,
+";
+        let rows = import_csv(csv.as_bytes(), dir.path(), "", &corpus);
+        let rules = rows
+            .iter()
+            .map(|row| {
+                row.findings
+                    .iter()
+                    .map(|finding| finding.rule)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rules,
+            [vec!["duplicate-test"], vec!["duplicate-isbn"], vec![]]
+        );
+        assert!(rows[0].findings[0].message.ends_with(", t"));
+    }
+}
@@ -1,11 +1,37 @@
+pub mod active_content;
+pub mod archive;
+pub mod backend;
+pub mod baseline;
+pub mod citation;
+pub mod corpus;
+pub mod coverage;
+pub mod dedup;
+pub mod export;
+pub mod file_index;
+pub mod file_lint;
+pub mod fix;
+#[cfg(test)]
+mod fixtures;
+pub mod fulltext;
+pub mod hygiene;
+pub mod import;
+pub mod malware;
 pub mod metadata;
+pub mod pdf;
+pub mod phash;
+pub mod query;
+pub mod rules;
+pub mod scaffold;
+pub mod stats;
+pub mod storage;
+pub mod validate;
 
 pub fn get_env(key: &str) -> String {
     std::env::var(key).unwrap_or_else(|_| panic!("Error: Environment variable {key} not set."))
 }
 
 pub fn get_env_or(key: &str,val:String) -> String {
-    std::env::var(key).unwrap_or_else(|_| val)
+    std::env::var(key).unwrap_or(val)
 }
 
 pub fn get_optional_env(key: &str) -> Option<String> {
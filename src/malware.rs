@@ -0,0 +1,293 @@
+use std::{
+    io::{Read, Write},
+    net::{TcpStream, ToSocketAddrs},
+    process::{Command, Stdio},
+    time::{Duration, Instant},
+};
+
+use crate::rules::Finding;
+
+const CHUNK_SIZE: usize = 64 * 1024;
+/// 连接、读写和等待扫描程序的超时, 扫描器无响应时不会一直阻塞检查
+pub const SCAN_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// 病毒扫描器, 通过环境变量 `MALWARE_SCANNER` 配置:
+///
+/// - `clamd:tcp:127.0.0.1:3310` 或 `clamd:unix:/run/clamav/clamd.ctl`: 使用 clamd 的 INSTREAM 命令
+/// - `command:程序 参数...`: 文件内容写入标准输入, 退出码 0 表示正常, 1 表示发现病毒
+///   (标准输出的第一行为病毒名), 其他退出码表示扫描失败, 与 `clamscan -` 一致
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Scanner {
+    ClamdTcp(String),
+    ClamdUnix(String),
+    Command(Vec<String>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScanResult {
+    Clean,
+    Infected(String),
+}
+
+impl Scanner {
+    pub fn parse(spec: &str) -> anyhow::Result<Self> {
+        if let Some(address) = spec.strip_prefix("clamd:tcp:") {
+            Ok(Self::ClamdTcp(address.to_string()))
+        } else if let Some(path) = spec.strip_prefix("clamd:unix:") {
+            Ok(Self::ClamdUnix(path.to_string()))
+        } else if let Some(command) = spec.strip_prefix("command:") {
+            let args = command
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            if args.is_empty() {
+                return Err(anyhow::anyhow!("Empty malware scanner command"));
+            }
+            Ok(Self::Command(args))
+        } else {
+            Err(anyhow::anyhow!("Unknown malware scanner: {spec}"))
+        }
+    }
+
+    pub fn scan(&self, bytes: &[u8]) -> anyhow::Result<ScanResult> {
+        self.scan_with_timeout(bytes, SCAN_TIMEOUT)
+    }
+
+    pub fn scan_with_timeout(&self, bytes: &[u8], timeout: Duration) -> anyhow::Result<ScanResult> {
+        match self {
+            Self::ClamdTcp(address) => {
+                let stream = connect_tcp(address, timeout)?;
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                clamd_scan(stream, bytes)
+            }
+            #[cfg(unix)]
+            Self::ClamdUnix(path) => {
+                let stream = std::os::unix::net::UnixStream::connect(path)?;
+                stream.set_read_timeout(Some(timeout))?;
+                stream.set_write_timeout(Some(timeout))?;
+                clamd_scan(stream, bytes)
+            }
+            #[cfg(not(unix))]
+            Self::ClamdUnix(_) => Err(anyhow::anyhow!("Unix sockets are not supported")),
+            Self::Command(args) => command_scan(args, bytes, timeout),
+        }
+    }
+
+    /// 扫描失败也作为问题报告, 配置了扫描器时不应放过未扫描的文件
+    pub fn check(&self, bytes: &[u8]) -> Option<Finding> {
+        match self.scan(bytes) {
+            Ok(ScanResult::Clean) => None,
+            Ok(ScanResult::Infected(name)) => Some(Finding::new(
+                "malware",
+                format!("病毒扫描发现恶意内容: {}", name),
+            )),
+            Err(e) => Some(Finding::new(
+                "malware-scan-failed",
+                format!("病毒扫描失败: {}", e),
+            )),
+        }
+    }
+}
+
+/// 依次尝试解析出的每个地址, 每次连接最多等待 `timeout`
+fn connect_tcp(address: &str, timeout: Duration) -> anyhow::Result<TcpStream> {
+    let mut last_error = None;
+    for address in address.to_socket_addrs()? {
+        match TcpStream::connect_timeout(&address, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_error = Some(e),
+        }
+    }
+    Err(match last_error {
+        Some(e) => e.into(),
+        None => anyhow::anyhow!("Cannot resolve clamd address: {address}"),
+    })
+}
+
+fn clamd_scan(mut stream: impl Read + Write, bytes: &[u8]) -> anyhow::Result<ScanResult> {
+    stream.write_all(b"zINSTREAM\0")?;
+    for chunk in bytes.chunks(CHUNK_SIZE) {
+        stream.write_all(&(chunk.len() as u32).to_be_bytes())?;
+        stream.write_all(chunk)?;
+    }
+    stream.write_all(&[0; 4])?;
+    stream.flush()?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    let response = String::from_utf8_lossy(&response);
+    let response = response.trim_end_matches(['\0', '\n']);
+    let status = response
+        .strip_prefix("stream: ")
+        .ok_or_else(|| anyhow::anyhow!("Unexpected clamd response: {response}"))?;
+    if status == "OK" {
+        Ok(ScanResult::Clean)
+    } else if let Some(name) = status.strip_suffix(" FOUND") {
+        Ok(ScanResult::Infected(name.to_string()))
+    } else {
+        Err(anyhow::anyhow!("clamd: {status}"))
+    }
+}
+
+fn read_in_background(mut pipe: impl Read + Send + 'static) -> std::thread::JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+fn command_scan(args: &[String], bytes: &[u8], timeout: Duration) -> anyhow::Result<ScanResult> {
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut stdin = child.stdin.take().unwrap();
+    let bytes = bytes.to_vec();
+    let writer = std::thread::spawn(move || stdin.write_all(&bytes));
+    let stdout = read_in_background(child.stdout.take().unwrap());
+    let stderr = read_in_background(child.stderr.take().unwrap());
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            // 不等待读写线程, 扫描程序的子进程可能仍占用着管道
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(anyhow::anyhow!("{} timed out after {:?}", args[0], timeout));
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    // 扫描器可能在读完输入前就给出结果, 此时写入失败可以忽略
+    let _ = writer.join();
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    match status.code() {
+        Some(0) => Ok(ScanResult::Clean),
+        Some(1) => {
+            let stdout = String::from_utf8_lossy(&stdout);
+            let name = stdout.lines().next().unwrap_or_default().trim();
+            Ok(ScanResult::Infected(if name.is_empty() {
+                "unknown".to_string()
+            } else {
+                name.to_string()
+            }))
+        }
+        _ => Err(anyhow::anyhow!(
+            "{} exited with {}: {}",
+            args[0],
+            status,
+            String::from_utf8_lossy(&stderr).trim()
+        )),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::net::TcpListener;
+
+    const EICAR: &[u8] = b"X5O!P%@AP[4\\PZX54(P^)7CC)7}$EICAR-STANDARD-ANTIVIRUS-TEST-FILE!$H+H*";
+
+    /// 按 INSTREAM 协议读取数据, 含有 EICAR 时报告发现病毒
+    fn fake_clamd() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut command = [0; 10];
+                stream.read_exact(&mut command).unwrap();
+                assert_eq!(&command, b"zINSTREAM\0");
+                let mut data = Vec::new();
+                loop {
+                    let mut length = [0; 4];
+                    stream.read_exact(&mut length).unwrap();
+                    let length = u32::from_be_bytes(length) as usize;
+                    if length == 0 {
+                        break;
+                    }
+                    let mut chunk = vec![0; length];
+                    stream.read_exact(&mut chunk).unwrap();
+                    data.extend(chunk);
+                }
+                let infected = data.windows(EICAR.len()).any(|window| window == EICAR);
+                let response: &[u8] = if infected {
+                    b"stream: Eicar-Test-Signature FOUND\0"
+                } else {
+                    b"stream: OK\0"
+                };
+                stream.write_all(response).unwrap();
+            }
+        });
+        address
+    }
+
+    #[test]
+    fn test_clamd() {
+        let scanner = Scanner::parse(&format!("clamd:tcp:{}", fake_clamd())).unwrap();
+        let mut large = vec![b'a'; CHUNK_SIZE * 2];
+        assert_eq!(scanner.scan(&large).unwrap(), ScanResult::Clean);
+        large.extend(EICAR);
+        assert_eq!(
+            scanner.scan(&large).unwrap(),
+            ScanResult::Infected("Eicar-Test-Signature".to_string())
+        );
+        assert_eq!(scanner.check(EICAR).unwrap().rule, "malware");
+    }
+
+    #[test]
+    fn test_clamd_timeout() {
+        // 接受连接但从不回复
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        std::thread::spawn(move || {
+            let streams = listener.incoming().collect::<Vec<_>>();
+            drop(streams);
+        });
+        let scanner = Scanner::parse(&format!("clamd:tcp:{}", address)).unwrap();
+        let started = Instant::now();
+        assert!(
+            scanner
+                .scan_with_timeout(b"%PDF-1.4", Duration::from_millis(200))
+                .is_err()
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_command() {
+        let scanner = Scanner::Command(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "if grep -q EICAR; then echo Eicar-Test-Signature; exit 1; fi".to_string(),
+        ]);
+        assert_eq!(scanner.scan(b"%PDF-1.4").unwrap(), ScanResult::Clean);
+        assert_eq!(
+            scanner.scan(EICAR).unwrap(),
+            ScanResult::Infected("Eicar-Test-Signature".to_string())
+        );
+        let failing = Scanner::Command(vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "echo broken >&2; exit 2".to_string(),
+        ]);
+        assert!(failing.scan(EICAR).is_err());
+        let missing = Scanner::parse("command:/nonexistent-scanner").unwrap();
+        assert_eq!(missing.check(b"").unwrap().rule, "malware-scan-failed");
+        assert!(Scanner::parse("virustotal").is_err());
+
+        let hanging = Scanner::Command(vec!["sleep".to_string(), "30".to_string()]);
+        let started = Instant::now();
+        let error = hanging
+            .scan_with_timeout(b"", Duration::from_millis(200))
+            .unwrap_err();
+        assert!(error.to_string().contains("timed out"));
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize, ser::SerializeStruct};
 
-#[derive(serde::Deserialize, Debug, Serialize, Clone)]
+#[derive(serde::Deserialize, Debug, Serialize, Clone, Copy, PartialEq, Eq)]
 #[serde(deny_unknown_fields)]
 pub enum Type {
     #[serde(rename = "test")]
@@ -11,6 +11,19 @@ pub enum Type {
     Doc,
 }
 
+impl std::str::FromStr for Type {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim() {
+            "test" => Ok(Type::Test),
+            "book" => Ok(Type::Book),
+            "doc" => Ok(Type::Doc),
+            other => Err(format!("未知的type: {:?}, 只能为test、book或doc", other)),
+        }
+    }
+}
+
 #[derive(serde::Deserialize, Debug, Serialize, Clone)]
 #[serde(deny_unknown_fields)]
 #[allow(dead_code)]
@@ -41,6 +54,23 @@ pub struct Test {
     pub content: Vec<String>,
     #[serde(skip_deserializing)]
     pub filesize: Option<i64>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<usize>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub has_text_layer: Option<bool>,
+}
+
+/// 统一为带连字符的 ISBN-13, 如 `978-7-111-40772-0`, 无法识别时原样返回
+pub fn hyphenate_isbn(isbn: &str) -> String {
+    let isbn13 = match isbn.parse::<isbn::Isbn>() {
+        Ok(isbn::Isbn::_10(isbn10)) => isbn::Isbn13::from(isbn10),
+        Ok(isbn::Isbn::_13(isbn13)) => isbn13,
+        Err(_) => return isbn.to_string(),
+    };
+    isbn13
+        .hyphenate()
+        .map(|isbn| isbn.to_string())
+        .unwrap_or_else(|_| isbn13.to_string())
 }
 
 #[derive(serde::Deserialize, Debug, Serialize, Clone)]
@@ -80,6 +110,10 @@ pub struct Book {
     pub filetype: String,
     #[serde(skip_deserializing)]
     pub filesize: Option<i64>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<usize>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub has_text_layer: Option<bool>,
 }
 
 #[derive(serde::Deserialize, Debug, Serialize, Clone)]
@@ -92,6 +126,10 @@ pub struct Doc {
     pub content: Vec<String>,
     #[serde(skip_deserializing)]
     pub filesize: Option<i64>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub page_count: Option<usize>,
+    #[serde(skip_deserializing, skip_serializing_if = "Option::is_none")]
+    pub has_text_layer: Option<bool>,
 }
 
 impl<'de> Deserialize<'de> for MetaData {
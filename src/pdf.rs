@@ -0,0 +1,364 @@
+use image::{DynamicImage, GrayImage};
+use pdfium_render::prelude::{
+    PdfBookmark, PdfDocument, PdfDocumentMetadataTagType, PdfPage, PdfPageObjectCommon,
+    PdfPageObjectsCommon, PdfRenderConfig, PdfSecurityHandlerRevision, Pdfium, PdfiumError,
+    PdfiumInternalError,
+};
+
+use serde::Serialize;
+
+use crate::rules::Finding;
+
+/// 优先使用 ./lib 下的 pdfium, 找不到时使用系统中的
+pub fn bind_pdfium() -> anyhow::Result<Pdfium> {
+    Ok(Pdfium::new(
+        Pdfium::bind_to_library(Pdfium::pdfium_platform_library_name_at_path("./lib"))
+            .or_else(|_| Pdfium::bind_to_system_library())?,
+    ))
+}
+
+/// 生成封面时使用的渲染参数, 感知哈希也基于同样的渲染结果
+pub fn cover_render_config() -> PdfRenderConfig {
+    PdfRenderConfig::new()
+        .set_target_width(2000)
+        .set_maximum_height(2000)
+}
+
+pub fn render_cover(document: &PdfDocument) -> anyhow::Result<DynamicImage> {
+    let page = document.pages().get(0)?;
+    Ok(page.render_with_config(&cover_render_config())?.as_image())
+}
+
+/// 提取所有页面的文字, 页面之间以换行分隔
+pub fn extract_text(document: &PdfDocument) -> anyhow::Result<String> {
+    extract_pages_text(document, usize::MAX)
+}
+
+/// 只提取前 `pages` 页的文字
+pub fn extract_pages_text(document: &PdfDocument, pages: usize) -> anyhow::Result<String> {
+    let mut text = String::new();
+    for page in document.pages().iter().take(pages) {
+        text.push_str(&page.text()?.all());
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+/// PDF 文档信息字典中的字段
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct DocumentInfo {
+    pub title: Option<String>,
+    pub author: Option<String>,
+    pub creation_date: Option<String>,
+}
+
+/// 读取文档信息字典中的标题、作者和创建日期, 空字段视为不存在
+pub fn read_document_info(document: &PdfDocument) -> DocumentInfo {
+    let get = |tag| {
+        document
+            .metadata()
+            .get(tag)
+            .map(|tag| tag.value().trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    DocumentInfo {
+        title: get(PdfDocumentMetadataTagType::Title),
+        author: get(PdfDocumentMetadataTagType::Author),
+        creation_date: get(PdfDocumentMetadataTagType::CreationDate),
+    }
+}
+
+/// 书签的最大层数和总数, 防止损坏的 PDF 中出现循环引用
+const MAX_OUTLINE_DEPTH: usize = 16;
+const MAX_OUTLINE_ITEMS: usize = 10000;
+
+/// PDF 书签中的一项, `page` 从 1 开始, 书签不指向本文档中的页面时为 None
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct OutlineItem {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub page: Option<u32>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<OutlineItem>,
+}
+
+fn bookmark_page(bookmark: &PdfBookmark) -> Option<u32> {
+    let page = match bookmark.destination() {
+        Some(destination) => destination.page_index().ok()?,
+        None => bookmark
+            .action()?
+            .as_local_destination_action()?
+            .destination()
+            .ok()?
+            .page_index()
+            .ok()?,
+    };
+    Some(page as u32 + 1)
+}
+
+fn read_outline(first: Option<PdfBookmark>, depth: usize, count: &mut usize) -> Vec<OutlineItem> {
+    let mut items = Vec::new();
+    let mut current = first;
+    while let Some(bookmark) = current {
+        if depth >= MAX_OUTLINE_DEPTH || *count >= MAX_OUTLINE_ITEMS {
+            break;
+        }
+        *count += 1;
+        items.push(OutlineItem {
+            title: bookmark.title().unwrap_or_default().trim().to_string(),
+            page: bookmark_page(&bookmark),
+            children: read_outline(bookmark.first_child(), depth + 1, count),
+        });
+        current = bookmark.next_sibling();
+    }
+    items
+}
+
+/// 读取 PDF 的书签树
+pub fn extract_outline(document: &PdfDocument) -> Vec<OutlineItem> {
+    read_outline(document.bookmarks().root(), 0, &mut 0)
+}
+
+/// 打开 PDF 并检查能否正常处理: 未加密且至少有一页
+pub fn open_pdf<'a>(pdfium: &'a Pdfium, bytes: &'a [u8]) -> Result<PdfDocument<'a>, Finding> {
+    let document = pdfium
+        .load_pdf_from_byte_slice(bytes, None)
+        .map_err(|e| match e {
+            PdfiumError::PdfiumLibraryInternalError(PdfiumInternalError::PasswordError) => {
+                Finding::new("pdf-encrypted", "PDF设置了打开密码, 请上传未加密的文件")
+            }
+            e => Finding::new("pdf-unreadable", format!("无法打开PDF: {:?}", e)),
+        })?;
+    if !matches!(
+        document.permissions().security_handler_revision(),
+        Ok(PdfSecurityHandlerRevision::Unprotected)
+    ) {
+        return Err(Finding::new(
+            "pdf-encrypted",
+            "PDF已加密 (设置了权限密码), 请上传未加密的文件",
+        ));
+    }
+    if document.pages().is_empty() {
+        return Err(Finding::new("pdf-empty", "PDF中没有任何页面"));
+    }
+    Ok(document)
+}
+
+/// 扫描件分辨率的默认下限
+pub const DEFAULT_MIN_DPI: f32 = 150.0;
+/// 判断空白页时渲染的宽度
+const BLANK_RENDER_WIDTH: i32 = 200;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PageQuality {
+    /// 页面上最大图片的有效分辨率, 没有图片时为 None
+    pub dpi: Option<f32>,
+    pub blank: bool,
+    pub has_text: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PdfQuality {
+    pub pages: Vec<PageQuality>,
+}
+
+/// 与背景 (中位数亮度) 相差明显的像素不足 0.5% 时认为是空白页, 对泛黄或偏灰的扫描纸张同样有效
+pub fn is_blank(image: &GrayImage) -> bool {
+    let mut histogram = [0usize; 256];
+    for pixel in image.pixels() {
+        histogram[pixel[0] as usize] += 1;
+    }
+    let total = image.width() as usize * image.height() as usize;
+    if total == 0 {
+        return true;
+    }
+    let mut seen = 0;
+    let background = histogram
+        .iter()
+        .position(|count| {
+            seen += count;
+            seen * 2 >= total
+        })
+        .unwrap_or_default() as i32;
+    let ink = image
+        .pixels()
+        .filter(|pixel| (pixel[0] as i32 - background).abs() > 48)
+        .count();
+    ink * 200 < total
+}
+
+/// 把页码列表写成 "1-3, 5" 的形式
+fn page_ranges(pages: &[usize]) -> String {
+    let mut ranges: Vec<(usize, usize)> = Vec::new();
+    for &page in pages {
+        match ranges.last_mut() {
+            Some((_, end)) if *end + 1 == page => *end = page,
+            _ => ranges.push((page, page)),
+        }
+    }
+    ranges
+        .iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl PdfQuality {
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn has_text_layer(&self) -> bool {
+        self.pages.iter().any(|page| page.has_text)
+    }
+
+    pub fn findings(&self, min_dpi: f32) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let low = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter_map(|(index, page)| Some((index + 1, page.dpi?)))
+            .filter(|(_, dpi)| *dpi < min_dpi)
+            .collect::<Vec<_>>();
+        if !low.is_empty() {
+            let lowest = low.iter().map(|(_, dpi)| *dpi).fold(f32::MAX, f32::min);
+            let pages = low.iter().map(|(page, _)| *page).collect::<Vec<_>>();
+            findings.push(Finding::new(
+                "pdf-low-resolution",
+                format!(
+                    "第 {} 页的分辨率较低 (最低约 {:.0} DPI, 建议不低于 {:.0} DPI), 可能难以看清",
+                    page_ranges(&pages),
+                    lowest,
+                    min_dpi
+                ),
+            ));
+        }
+        let blank = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter(|(_, page)| page.blank)
+            .map(|(index, _)| index + 1)
+            .collect::<Vec<_>>();
+        if !blank.is_empty() {
+            findings.push(Finding::new(
+                "pdf-blank-page",
+                format!("第 {} 页可能是空白页", page_ranges(&blank)),
+            ));
+        }
+        if !self.has_text_layer() {
+            findings.push(Finding::new(
+                "pdf-no-text-layer",
+                "PDF没有文字层, 无法搜索和复制文字",
+            ));
+        }
+        findings
+    }
+}
+
+fn page_has_text(page: &PdfPage) -> bool {
+    page.text()
+        .map(|text| text.all().chars().any(|c| !c.is_whitespace()))
+        .unwrap_or(false)
+}
+
+/// 是否有任何一页含有文字, 不渲染页面, 发布时用它代替完整的质量分析
+pub fn has_text_layer(document: &PdfDocument) -> bool {
+    document.pages().iter().any(|page| page_has_text(&page))
+}
+
+/// 逐页分析扫描质量: 图片分辨率、是否空白、是否有文字
+pub fn analyze_quality(document: &PdfDocument) -> anyhow::Result<PdfQuality> {
+    let render_config = PdfRenderConfig::new().set_target_width(BLANK_RENDER_WIDTH);
+    let mut pages = Vec::new();
+    for page in document.pages().iter() {
+        let mut dpi = None;
+        let mut largest = 0.0;
+        for object in page.objects().iter() {
+            let Some(image) = object.as_image_object() else {
+                continue;
+            };
+            let (Ok(width), Ok(height)) = (object.width(), object.height()) else {
+                continue;
+            };
+            let area = width.value * height.value;
+            if area > largest
+                && let (Ok(horizontal), Ok(vertical)) =
+                    (image.horizontal_dpi(), image.vertical_dpi())
+            {
+                largest = area;
+                dpi = Some(horizontal.min(vertical));
+            }
+        }
+        let has_text = page_has_text(&page);
+        let blank = !has_text
+            && is_blank(
+                &page
+                    .render_with_config(&render_config)?
+                    .as_image()
+                    .to_luma8(),
+            );
+        pages.push(PageQuality {
+            dpi,
+            blank,
+            has_text,
+        });
+    }
+    Ok(PdfQuality { pages })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::Luma;
+
+    #[test]
+    fn test_is_blank() {
+        let paper = GrayImage::from_pixel(200, 280, Luma([225]));
+        assert!(is_blank(&paper));
+        let mut text = paper.clone();
+        for x in 20..180 {
+            for y in (20..260).step_by(12) {
+                text.put_pixel(x, y, Luma([30]));
+            }
+        }
+        assert!(!is_blank(&text));
+    }
+
+    #[test]
+    fn test_quality_findings() {
+        let page = |dpi, blank, has_text| PageQuality {
+            dpi,
+            blank,
+            has_text,
+        };
+        let quality = PdfQuality {
+            pages: vec![
+                page(Some(72.0), false, false),
+                page(Some(96.0), false, false),
+                page(Some(300.0), true, false),
+                page(Some(100.0), false, false),
+            ],
+        };
+        let findings = quality.findings(DEFAULT_MIN_DPI);
+        assert_eq!(
+            findings[0].message,
+            "第 1-2, 4 页的分辨率较低 (最低约 72 DPI, 建议不低于 150 DPI), 可能难以看清"
+        );
+        assert_eq!(findings[1].message, "第 3 页可能是空白页");
+        assert_eq!(findings[2].rule, "pdf-no-text-layer");
+
+        let quality = PdfQuality {
+            pages: vec![page(None, false, true)],
+        };
+        assert!(quality.findings(DEFAULT_MIN_DPI).is_empty());
+        assert_eq!(quality.page_count(), 1);
+    }
+}
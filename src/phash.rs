@@ -0,0 +1,58 @@
+use image::{DynamicImage, imageops::FilterType};
+
+/// 两个哈希的汉明距离不超过该值时认为是同一份文件的不同扫描
+pub const DEFAULT_MAX_DISTANCE: u32 = 10;
+
+/// 计算图片的 dHash: 缩放为 9x8 的灰度图, 比较每行相邻像素的亮度
+///
+/// 重新扫描、重新保存或轻微的缩放和压缩只会改变少数几位
+pub fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+pub fn distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
+pub fn to_hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+pub fn from_hex(hash: &str) -> Option<u64> {
+    u64::from_str_radix(hash, 16).ok()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use image::{GrayImage, Luma};
+
+    fn page(f: impl Fn(u32, u32) -> u8) -> DynamicImage {
+        DynamicImage::ImageLuma8(GrayImage::from_fn(300, 400, |x, y| Luma([f(x, y)])))
+    }
+
+    #[test]
+    fn test_dhash() {
+        let original = page(|x, y| ((x * 7 + y * 3) % 256) as u8);
+        let rescanned = page(|x, y| ((x * 7 + y * 3) % 256) as u8 / 2 + 60);
+        let resized =
+            DynamicImage::ImageLuma8(original.resize(150, 200, FilterType::Nearest).to_luma8());
+        let other = page(|x, y| ((x * y) % 97) as u8);
+
+        let hash = dhash(&original);
+        assert!(distance(hash, dhash(&rescanned)) <= DEFAULT_MAX_DISTANCE);
+        assert!(distance(hash, dhash(&resized)) <= DEFAULT_MAX_DISTANCE);
+        assert!(distance(hash, dhash(&other)) > DEFAULT_MAX_DISTANCE);
+        assert_eq!(from_hex(&to_hex(hash)), Some(hash));
+    }
+}
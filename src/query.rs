@@ -0,0 +1,295 @@
+use crate::{
+    export::{Flat, flatten},
+    hygiene::is_cjk,
+    metadata::*,
+};
+
+/// 可以用 `--missing` 查询的字段
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Field {
+    Title,
+    Authors,
+    Translators,
+    Edition,
+    PublishYear,
+    Publisher,
+    Isbn,
+    Course,
+    CourseType,
+    College,
+    Semester,
+    Stage,
+    Content,
+}
+
+impl Field {
+    /// 该类型的元信息是否有这个字段
+    fn applies_to(self, type_: Type) -> bool {
+        match self {
+            Field::Title => type_ != Type::Test,
+            Field::Authors
+            | Field::Translators
+            | Field::Edition
+            | Field::PublishYear
+            | Field::Publisher
+            | Field::Isbn => type_ == Type::Book,
+            Field::Course | Field::CourseType | Field::Content => type_ != Type::Book,
+            Field::College | Field::Semester | Field::Stage => type_ == Type::Test,
+        }
+    }
+
+    fn is_missing(self, flat: &Flat) -> bool {
+        let empty = |value: Option<&str>| value.is_none_or(|value| value.trim().is_empty());
+        match self {
+            Field::Title => empty(flat.title),
+            Field::Authors => flat.authors.is_empty(),
+            Field::Translators => flat.translators.is_empty(),
+            Field::Edition => empty(flat.edition),
+            Field::PublishYear => empty(flat.publish_year),
+            Field::Publisher => empty(flat.publisher),
+            Field::Isbn => flat.isbn.is_empty(),
+            Field::Course => flat.courses.is_empty(),
+            Field::CourseType => flat
+                .courses
+                .iter()
+                .all(|course| empty(course.type_.as_deref())),
+            Field::College => flat.colleges.is_empty(),
+            Field::Semester => empty(flat.time.and_then(|time| time.semester.as_deref())),
+            Field::Stage => empty(flat.time.and_then(|time| time.stage.as_deref())),
+            Field::Content => flat.content.is_empty(),
+        }
+    }
+}
+
+/// 查询条件, 所有条件同时满足时匹配
+#[derive(clap::Args, Debug, Default)]
+pub struct Filter {
+    /// 元信息的类型: test、book 或 doc
+    #[arg(long = "type")]
+    pub type_: Option<Type>,
+    /// 课程名包含该文字
+    #[arg(long)]
+    pub course: Option<String>,
+    /// 学院名包含该文字
+    #[arg(long)]
+    pub college: Option<String>,
+    /// 试卷的学年或书籍的出版年份不早于该年
+    #[arg(long)]
+    pub from_year: Option<i32>,
+    /// 试卷的学年或书籍的出版年份不晚于该年
+    #[arg(long)]
+    pub to_year: Option<i32>,
+    /// content 中包含该项, 可重复
+    #[arg(long = "content")]
+    pub contents: Vec<String>,
+    /// content 中不包含该项, 可重复
+    #[arg(long = "without-content")]
+    pub without_contents: Vec<String>,
+    /// 缺少该字段, 只匹配有该字段的类型, 可重复
+    #[arg(long, value_enum)]
+    pub missing: Vec<Field>,
+}
+
+/// 试卷的学年或书籍的出版年份, 资料和无法解析的年份为空
+fn years(flat: &Flat) -> Option<(i32, i32)> {
+    match (flat.time, flat.publish_year) {
+        (Some(time), _) => Some((time.start.parse().ok()?, time.end.parse().ok()?)),
+        (None, Some(year)) => {
+            let year = year.parse().ok()?;
+            Some((year, year))
+        }
+        (None, None) => None,
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, metadata: &MetaData) -> bool {
+        let flat = flatten(metadata);
+        if self.type_.is_some_and(|type_| type_ != metadata.type_) {
+            return false;
+        }
+        if let Some(course) = &self.course
+            && !flat
+                .courses
+                .iter()
+                .any(|c| c.name.contains(course.as_str()))
+        {
+            return false;
+        }
+        if let Some(college) = &self.college
+            && !flat.colleges.iter().any(|c| c.contains(college.as_str()))
+        {
+            return false;
+        }
+        if self.from_year.is_some() || self.to_year.is_some() {
+            let Some((start, end)) = years(&flat) else {
+                return false;
+            };
+            if self.from_year.is_some_and(|from| end < from)
+                || self.to_year.is_some_and(|to| start > to)
+            {
+                return false;
+            }
+        }
+        if !self.contents.iter().all(|c| flat.content.contains(c))
+            || self
+                .without_contents
+                .iter()
+                .any(|c| flat.content.contains(c))
+        {
+            return false;
+        }
+        self.missing
+            .iter()
+            .all(|field| field.applies_to(metadata.type_) && field.is_missing(&flat))
+    }
+}
+
+/// 按条件筛选元信息, 保持原有顺序
+pub fn query<'a>(records: &'a [MetaData], filter: &Filter) -> Vec<&'a MetaData> {
+    records
+        .iter()
+        .filter(|metadata| filter.matches(metadata))
+        .collect()
+}
+
+/// 终端中的显示宽度, 中文和全角字符占两列
+fn display_width(value: &str) -> usize {
+    value
+        .chars()
+        .map(|c| match c {
+            '\u{3000}'..='\u{303F}' | '\u{FF00}'..='\u{FF60}' => 2,
+            c if is_cjk(c) => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+/// 表格的一行: id、类型、书名或课程、时间、内容
+fn table_row(metadata: &MetaData) -> [String; 5] {
+    let flat = flatten(metadata);
+    let title = match flat.title {
+        Some(title) => title.to_string(),
+        None => flat
+            .courses
+            .iter()
+            .map(|course| course.name.as_str())
+            .collect::<Vec<_>>()
+            .join("; "),
+    };
+    let time = match (flat.time, flat.publish_year) {
+        (Some(time), _) => [
+            Some(format!("{}-{}", time.start, time.end)),
+            time.semester.clone(),
+            time.stage.clone(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>()
+        .join(" "),
+        (None, year) => year.unwrap_or_default().to_string(),
+    };
+    [
+        metadata.id.clone(),
+        flat.type_.to_string(),
+        title,
+        time,
+        flat.content.join("; "),
+    ]
+}
+
+/// 以对齐的文本表格输出, 第一行为表头
+pub fn to_table(records: &[&MetaData]) -> String {
+    let header = ["id", "type", "title", "time", "content"].map(str::to_string);
+    let rows = std::iter::once(header)
+        .chain(records.iter().map(|metadata| table_row(metadata)))
+        .collect::<Vec<_>>();
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(display_width(cell));
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let mut line = String::new();
+        for (width, cell) in widths.iter().zip(row) {
+            line.push_str(cell);
+            line.push_str(&" ".repeat(width - display_width(cell) + 2));
+        }
+        table.push_str(line.trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::records;
+
+    fn ids(records: &[MetaData], filter: Filter) -> Vec<&str> {
+        query(records, &filter)
+            .iter()
+            .map(|metadata| metadata.id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_query() {
+        let records = records();
+        let all = ids(&records, Filter::default());
+        assert_eq!(all, ["a", "b", "c", "d", "e"]);
+        let filter = Filter {
+            course: Some("数据".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&records, filter), ["a", "d"]);
+        let filter = Filter {
+            type_: Some(Type::Test),
+            without_contents: vec!["答案".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(ids(&records, filter), ["b"]);
+        let filter = Filter {
+            from_year: Some(2023),
+            ..Default::default()
+        };
+        assert_eq!(ids(&records, filter), ["a", "b"]);
+        let filter = Filter {
+            to_year: Some(2020),
+            ..Default::default()
+        };
+        assert_eq!(ids(&records, filter), ["c"]);
+        let filter = Filter {
+            missing: vec![Field::Publisher, Field::Isbn],
+            ..Default::default()
+        };
+        assert_eq!(ids(&records, filter), ["e"]);
+        let filter = Filter {
+            missing: vec![Field::College],
+            ..Default::default()
+        };
+        assert_eq!(ids(&records, filter), ["b"]);
+        let filter = Filter {
+            college: Some("计算机".to_string()),
+            contents: vec!["原题".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(ids(&records, filter), ["a"]);
+    }
+
+    #[test]
+    fn test_to_table() {
+        let records = records();
+        let table = to_table(&query(&records, &Filter::default())[..2]);
+        assert_eq!(
+            table,
+            "\
+id  type  title      time                  content
+a   test  数据结构   2022-2023 期末        原题; 答案
+b   test  高等数学A  2023-2024 First 期中  原题
+"
+        );
+    }
+}
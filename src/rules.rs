@@ -0,0 +1,551 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+/// 规则的严重程度, `error` 会导致检查不通过, `warning` 仅提示, `off` 表示不启用该规则
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Off,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Off => write!(f, "off"),
+        }
+    }
+}
+
+pub struct Rule {
+    pub id: &'static str,
+    pub default: Severity,
+    pub description: &'static str,
+}
+
+pub const RULES: &[Rule] = &[
+    Rule {
+        id: "file-extension",
+        default: Severity::Error,
+        description: "元信息文件后缀只能为.yml",
+    },
+    Rule {
+        id: "parse-error",
+        default: Severity::Error,
+        description: "元信息无法解析",
+    },
+    Rule {
+        id: "invalid-suppression",
+        default: Severity::Warning,
+        description: "行内注释中引用了不存在的规则",
+    },
+    Rule {
+        id: "yaml-encoding",
+        default: Severity::Error,
+        description: "元信息文件不是有效的UTF-8编码",
+    },
+    Rule {
+        id: "yaml-bom",
+        default: Severity::Warning,
+        description: "元信息文件开头含有BOM",
+    },
+    Rule {
+        id: "yaml-crlf",
+        default: Severity::Warning,
+        description: "元信息文件使用了CRLF换行",
+    },
+    Rule {
+        id: "yaml-tab",
+        default: Severity::Error,
+        description: "元信息文件中含有制表符",
+    },
+    Rule {
+        id: "yaml-multi-document",
+        default: Severity::Error,
+        description: "元信息文件中含有多个YAML文档",
+    },
+    Rule {
+        id: "yaml-anchor",
+        default: Severity::Error,
+        description: "元信息文件中使用了锚点、别名或合并键",
+    },
+    Rule {
+        id: "yaml-duplicate-key",
+        default: Severity::Error,
+        description: "元信息文件中含有重复的键",
+    },
+    Rule {
+        id: "yaml-file-size",
+        default: Severity::Error,
+        description: "元信息文件过大",
+    },
+    Rule {
+        id: "file-uploaded",
+        default: Severity::Error,
+        description: "文件未上传或无法下载",
+    },
+    Rule {
+        id: "url-format",
+        default: Severity::Error,
+        description: "url格式不正确",
+    },
+    Rule {
+        id: "url-id-mismatch",
+        default: Severity::Error,
+        description: "url中文件名与id不匹配",
+    },
+    Rule {
+        id: "filename-id-mismatch",
+        default: Severity::Error,
+        description: "元信息文件名与id不匹配",
+    },
+    Rule {
+        id: "shard-location",
+        default: Severity::Error,
+        description: "分片布局中元信息文件不在以md5前两位命名的子目录中",
+    },
+    Rule {
+        id: "duplicate-id",
+        default: Severity::Error,
+        description: "不同子目录中存在同名的元信息文件",
+    },
+    Rule {
+        id: "upload-status",
+        default: Severity::Error,
+        description: "文件上传状态异常",
+    },
+    Rule {
+        id: "md5-mismatch",
+        default: Severity::Error,
+        description: "文件md5与id不匹配",
+    },
+    Rule {
+        id: "filetype",
+        default: Severity::Error,
+        description: "filetype取值不正确",
+    },
+    Rule {
+        id: "course-type",
+        default: Severity::Error,
+        description: "course type只能为\"本科\"或\"研究生\"",
+    },
+    Rule {
+        id: "empty-course",
+        default: Severity::Error,
+        description: "course不能为空",
+    },
+    Rule {
+        id: "empty-content",
+        default: Severity::Error,
+        description: "content不能为空",
+    },
+    Rule {
+        id: "content",
+        default: Severity::Error,
+        description: "content取值不正确",
+    },
+    Rule {
+        id: "stage",
+        default: Severity::Error,
+        description: "stage只能为\"期中\"或\"期末\"",
+    },
+    Rule {
+        id: "semester",
+        default: Severity::Error,
+        description: "semester只能为\"First\"或\"Second\"",
+    },
+    Rule {
+        id: "time",
+        default: Severity::Error,
+        description: "考试时间格式或范围不正确",
+    },
+    Rule {
+        id: "empty-college",
+        default: Severity::Error,
+        description: "college不能存在空字符串",
+    },
+    Rule {
+        id: "book-authors",
+        default: Severity::Error,
+        description: "书籍应当至少有一个作者",
+    },
+    Rule {
+        id: "publish-year",
+        default: Severity::Error,
+        description: "出版年份格式不正确",
+    },
+    Rule {
+        id: "isbn-format",
+        default: Severity::Error,
+        description: "isbn格式不正确",
+    },
+    Rule {
+        id: "duplicate-isbn",
+        default: Severity::Error,
+        description: "isbn与其他书籍重复",
+    },
+    Rule {
+        id: "duplicate-value",
+        default: Severity::Warning,
+        description: "列表字段中存在重复的值",
+    },
+    Rule {
+        id: "duplicate-test",
+        default: Severity::Warning,
+        description: "可能与其他试卷重复",
+    },
+    Rule {
+        id: "pdf-unreadable",
+        default: Severity::Error,
+        description: "PDF无法打开",
+    },
+    Rule {
+        id: "pdf-encrypted",
+        default: Severity::Error,
+        description: "PDF已加密",
+    },
+    Rule {
+        id: "pdf-empty",
+        default: Severity::Error,
+        description: "PDF没有任何页面",
+    },
+    Rule {
+        id: "pdf-low-resolution",
+        default: Severity::Warning,
+        description: "PDF中扫描图片的分辨率过低",
+    },
+    Rule {
+        id: "pdf-blank-page",
+        default: Severity::Warning,
+        description: "PDF中有空白页",
+    },
+    Rule {
+        id: "pdf-no-text-layer",
+        default: Severity::Warning,
+        description: "PDF没有文字层",
+    },
+    Rule {
+        id: "pdf-javascript",
+        default: Severity::Error,
+        description: "PDF中含有JavaScript",
+    },
+    Rule {
+        id: "pdf-launch-action",
+        default: Severity::Error,
+        description: "PDF中含有启动外部程序的动作",
+    },
+    Rule {
+        id: "pdf-embedded-file",
+        default: Severity::Error,
+        description: "PDF中含有嵌入的文件",
+    },
+    Rule {
+        id: "pdf-auto-action",
+        default: Severity::Warning,
+        description: "PDF中含有自动执行的动作",
+    },
+    Rule {
+        id: "zip-unreadable",
+        default: Severity::Error,
+        description: "压缩包无法打开或解压",
+    },
+    Rule {
+        id: "zip-path-traversal",
+        default: Severity::Error,
+        description: "压缩包中的文件路径指向压缩包之外",
+    },
+    Rule {
+        id: "zip-bomb",
+        default: Severity::Error,
+        description: "压缩包解压后过大或压缩率异常",
+    },
+    Rule {
+        id: "zip-empty",
+        default: Severity::Error,
+        description: "压缩包中没有有效文件",
+    },
+    Rule {
+        id: "zip-nested-archive",
+        default: Severity::Warning,
+        description: "压缩包中含有其他压缩包",
+    },
+    Rule {
+        id: "zip-executable",
+        default: Severity::Error,
+        description: "压缩包中含有可执行文件",
+    },
+    Rule {
+        id: "zip-filename-encoding",
+        default: Severity::Warning,
+        description: "压缩包中的文件名编码无法识别",
+    },
+    Rule {
+        id: "malware",
+        default: Severity::Error,
+        description: "病毒扫描发现恶意内容",
+    },
+    Rule {
+        id: "malware-scan-failed",
+        default: Severity::Error,
+        description: "病毒扫描失败",
+    },
+    Rule {
+        id: "similar-file",
+        default: Severity::Warning,
+        description: "文件第一页与已有文件相近",
+    },
+    Rule {
+        id: "text-zero-width",
+        default: Severity::Warning,
+        description: "文本中含有零宽字符",
+    },
+    Rule {
+        id: "text-whitespace",
+        default: Severity::Warning,
+        description: "文本首尾有空白或含有连续空白",
+    },
+    Rule {
+        id: "text-traditional",
+        default: Severity::Warning,
+        description: "文本中含有繁体字",
+    },
+    Rule {
+        id: "text-parenthesis",
+        default: Severity::Warning,
+        description: "中文文本应使用全角括号, 其他文本应使用半角括号",
+    },
+    Rule {
+        id: "text-cjk-spacing",
+        default: Severity::Warning,
+        description: "同一字段中中英文之间的空格不一致",
+    },
+];
+
+pub fn find_rule(id: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|rule| rule.id == id)
+}
+
+/// 某条规则产生的一条检查结果, 严重程度由配置和行内注释决定
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub message: String,
+    /// 同一文件中区分同一规则多个问题的稳定标识, 如字段名或 isbn, 基线按它而不是消息匹配
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+}
+
+impl Finding {
+    pub fn new(rule: &'static str, message: impl Into<String>) -> Self {
+        debug_assert!(find_rule(rule).is_some(), "unknown rule: {rule}");
+        Self {
+            rule,
+            message: message.into(),
+            key: None,
+        }
+    }
+
+    pub fn with_key(mut self, key: impl Into<String>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub message: String,
+    pub key: Option<String>,
+    pub severity: Severity,
+    pub suppressed: bool,
+    pub baselined: bool,
+}
+
+impl Diagnostic {
+    /// 未被行内注释豁免且不在基线中
+    pub fn is_active(&self) -> bool {
+        !self.suppressed && !self.baselined
+    }
+
+    /// 只有生效的 error 才会导致检查不通过
+    pub fn is_blocking(&self) -> bool {
+        self.severity == Severity::Error && self.is_active()
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.suppressed {
+            write!(
+                f,
+                "allowed({})[{}]: {}",
+                self.severity, self.rule, self.message
+            )
+        } else if self.baselined {
+            write!(
+                f,
+                "baselined({})[{}]: {}",
+                self.severity, self.rule, self.message
+            )
+        } else {
+            write!(f, "{}[{}]: {}", self.severity, self.rule, self.message)
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    #[serde(default)]
+    pub rules: HashMap<String, Severity>,
+    /// 元信息文件大小上限, 单位为字节
+    pub max_file_size: Option<u64>,
+    /// 感知哈希的汉明距离不超过该值时认为文件相近
+    pub phash_distance: Option<u32>,
+    /// 扫描件分辨率下限, 单位为 DPI
+    pub min_dpi: Option<f32>,
+    /// 压缩包解压后总大小上限, 单位为字节
+    pub zip_max_unpacked_size: Option<u64>,
+    /// 压缩包中单个文件压缩率上限
+    pub zip_max_ratio: Option<u64>,
+}
+
+impl Config {
+    pub fn parse(source: &str) -> anyhow::Result<Self> {
+        let config: Config = serde_yaml::from_str(source)?;
+        for id in config.rules.keys() {
+            if find_rule(id).is_none() {
+                return Err(anyhow::anyhow!("Unknown rule in config: {id}"));
+            }
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let source = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config {:?}: {}", path, e))?;
+        Self::parse(&source)
+    }
+
+    pub fn severity(&self, rule: &str) -> Severity {
+        self.rules
+            .get(rule)
+            .copied()
+            .or_else(|| find_rule(rule).map(|rule| rule.default))
+            .unwrap_or(Severity::Error)
+    }
+
+    /// 根据配置决定每条结果的严重程度, 关闭的规则直接丢弃, 被豁免的结果仍然保留以便在报告中列出
+    pub fn resolve(&self, findings: Vec<Finding>, suppressions: &Suppressions) -> Vec<Diagnostic> {
+        findings
+            .into_iter()
+            .filter_map(|finding| {
+                let severity = self.severity(finding.rule);
+                if severity == Severity::Off {
+                    return None;
+                }
+                Some(Diagnostic {
+                    rule: finding.rule,
+                    suppressed: suppressions.allows(finding.rule),
+                    baselined: false,
+                    message: finding.message,
+                    key: finding.key,
+                    severity,
+                })
+            })
+            .collect()
+    }
+}
+
+const SUPPRESSION_PREFIX: &str = "byrdocs-check:";
+
+/// 元信息文件中的行内豁免, 例如 `# byrdocs-check: allow duplicate-isbn`
+#[derive(Debug, Default)]
+pub struct Suppressions {
+    rules: Vec<&'static str>,
+    pub findings: Vec<Finding>,
+}
+
+impl Suppressions {
+    pub fn parse(source: &str) -> Self {
+        let mut suppressions = Suppressions::default();
+        for line in source.lines() {
+            let Some((_, comment)) = line.split_once('#') else {
+                continue;
+            };
+            let Some(directive) = comment.trim().strip_prefix(SUPPRESSION_PREFIX) else {
+                continue;
+            };
+            let Some(ids) = directive.trim().strip_prefix("allow") else {
+                suppressions.findings.push(Finding::new(
+                    "invalid-suppression",
+                    format!("无法识别的注释: {}", line.trim()),
+                ));
+                continue;
+            };
+            for id in ids.split([',', ' ']).filter(|id| !id.is_empty()) {
+                match find_rule(id) {
+                    Some(rule) => suppressions.rules.push(rule.id),
+                    None => suppressions.findings.push(
+                        Finding::new("invalid-suppression", format!("不存在的规则: {id}"))
+                            .with_key(id),
+                    ),
+                }
+            }
+        }
+        suppressions
+    }
+
+    pub fn allows(&self, rule: &str) -> bool {
+        self.rules.contains(&rule)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config() {
+        let config = Config::parse("rules:\n  duplicate-isbn: warning\n  time: off\n").unwrap();
+        assert_eq!(config.severity("duplicate-isbn"), Severity::Warning);
+        assert_eq!(config.severity("time"), Severity::Off);
+        assert_eq!(config.severity("md5-mismatch"), Severity::Error);
+        assert!(Config::parse("rules:\n  no-such-rule: warning\n").is_err());
+    }
+
+    #[test]
+    fn test_suppressions() {
+        let source = "# byrdocs-check: allow duplicate-isbn, isbn-format\nid: abc # byrdocs-check: allow nothing\n";
+        let suppressions = Suppressions::parse(source);
+        assert!(suppressions.allows("duplicate-isbn"));
+        assert!(suppressions.allows("isbn-format"));
+        assert!(!suppressions.allows("time"));
+        assert_eq!(suppressions.findings.len(), 1);
+        assert_eq!(suppressions.findings[0].rule, "invalid-suppression");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let config = Config::parse("rules:\n  time: off\n  stage: warning\n").unwrap();
+        let suppressions = Suppressions::parse("# byrdocs-check: allow content\n");
+        let diagnostics = config.resolve(
+            vec![
+                Finding::new("time", "请检查时间"),
+                Finding::new("stage", "请检查stage"),
+                Finding::new("content", "错误的content"),
+                Finding::new("filetype", "请检查filetype"),
+            ],
+            &suppressions,
+        );
+        assert_eq!(diagnostics.len(), 3);
+        assert!(!diagnostics[0].is_blocking());
+        assert!(diagnostics[1].suppressed);
+        assert!(!diagnostics[1].is_blocking());
+        assert!(diagnostics[2].is_blocking());
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::HashSet;
+
+use crate::{
+    backend::{Status, TempFiles},
+    metadata::*,
+    pdf::DocumentInfo,
+};
+
+/// 生成的草稿文件开头的注释, 提醒提交前核对
+pub const DRAFT_HEADER: &str = "# 自动生成的草稿, 请核对并补全后再提交\n";
+
+pub fn file_url(site_url: &str, md5: &str, filetype: &str) -> String {
+    format!(
+        "{}/files/{}.{}",
+        site_url.trim_end_matches('/'),
+        md5,
+        filetype
+    )
+}
+
+/// 根据文件头判断文件类型, 只支持 pdf 和 zip
+pub fn detect_filetype(bytes: &[u8]) -> Option<&'static str> {
+    if bytes.starts_with(b"%PDF-") {
+        Some("pdf")
+    } else if bytes.starts_with(b"PK\x03\x04") || bytes.starts_with(b"PK\x05\x06") {
+        Some("zip")
+    } else {
+        None
+    }
+}
+
+/// 新建元信息时由命令行填写的字段, 与类型无关的字段会被忽略
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct Fields {
+    /// 书籍或资料的标题
+    #[arg(long)]
+    pub title: Option<String>,
+    /// 作者, 可以重复
+    #[arg(long = "author")]
+    pub authors: Vec<String>,
+    /// 译者, 可以重复
+    #[arg(long = "translator")]
+    pub translators: Vec<String>,
+    #[arg(long)]
+    pub edition: Option<String>,
+    #[arg(long)]
+    pub publish_year: Option<String>,
+    #[arg(long)]
+    pub publisher: Option<String>,
+    /// ISBN, 可以重复
+    #[arg(long = "isbn")]
+    pub isbns: Vec<String>,
+    /// 课程名, 资料可以属于多门课程
+    #[arg(long = "course")]
+    pub courses: Vec<String>,
+    /// 课程类型: 本科或研究生
+    #[arg(long)]
+    pub course_type: Option<String>,
+    /// 学院, 可以重复
+    #[arg(long = "college")]
+    pub colleges: Vec<String>,
+    /// 开始学年, 如 2023
+    #[arg(long)]
+    pub start: Option<String>,
+    /// 结束学年, 不填时与开始学年相同
+    #[arg(long)]
+    pub end: Option<String>,
+    /// First 或 Second
+    #[arg(long)]
+    pub semester: Option<String>,
+    /// 期中或期末
+    #[arg(long)]
+    pub stage: Option<String>,
+    /// 内容, 可以重复
+    #[arg(long = "content")]
+    pub content: Vec<String>,
+}
+
+/// 生成指定类型的元信息, `id` 和 `url` 由文件的 md5 和类型得出, 未填写的字段留空
+pub fn draft(type_: Type, md5: &str, filetype: &str, site_url: &str, fields: Fields) -> MetaData {
+    let course = |name: String| Course {
+        type_: fields.course_type.clone(),
+        name,
+    };
+    let data = match type_ {
+        Type::Test => Data::Test(Test {
+            college: Some(fields.colleges),
+            course: course(fields.courses.into_iter().next().unwrap_or_default()),
+            time: Time {
+                end: fields
+                    .end
+                    .or_else(|| fields.start.clone())
+                    .unwrap_or_default(),
+                start: fields.start.unwrap_or_default(),
+                semester: fields.semester,
+                stage: fields.stage,
+            },
+            filetype: filetype.to_string(),
+            content: fields.content,
+            filesize: None,
+            page_count: None,
+            has_text_layer: None,
+        }),
+        Type::Book => Data::Book(Book {
+            title: fields.title.unwrap_or_default(),
+            authors: fields.authors,
+            translators: Some(fields.translators),
+            edition: fields.edition,
+            publish_year: fields.publish_year,
+            publisher: fields.publisher,
+            isbn: fields.isbns,
+            filetype: filetype.to_string(),
+            filesize: None,
+            page_count: None,
+            has_text_layer: None,
+        }),
+        Type::Doc => Data::Doc(Doc {
+            title: fields.title.unwrap_or_default(),
+            filetype: filetype.to_string(),
+            course: fields.courses.into_iter().map(course).collect(),
+            content: fields.content,
+            filesize: None,
+            page_count: None,
+            has_text_layer: None,
+        }),
+    };
+    MetaData {
+        id: md5.to_string(),
+        url: file_url(site_url, md5, filetype),
+        type_,
+        data,
+    }
+}
+
+/// 已经上传、还没有元信息的文件, 返回文件和它的 md5、文件类型
+pub fn missing_metadata<'a>(
+    files: &'a [TempFiles],
+    existing: &HashSet<String>,
+) -> Vec<(&'a TempFiles, &'a str, &'a str)> {
+    files
+        .iter()
+        .filter(|file| matches!(file.status, Status::Uploaded))
+        .filter_map(|file| {
+            let (md5, filetype) = file.file_name.split_once('.')?;
+            (md5.len() == 32 && ["pdf", "zip"].contains(&filetype) && !existing.contains(md5))
+                .then_some((file, md5, filetype))
+        })
+        .collect()
+}
+
+/// 记录上传者和上传时间, 方便整理时联系上传者
+pub fn upload_comment(file: &TempFiles) -> String {
+    format!(
+        "# 上传者: {}, 上传时间: {}\n",
+        file.uploader,
+        file.upload_time.as_deref().unwrap_or(&file.created_at)
+    )
+}
+
+/// 去掉值为空的字段, 如没有填写的 semester
+fn remove_nulls(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(mapping) => {
+            mapping.retain(|_, value| !value.is_null());
+            for (_, value) in mapping.iter_mut() {
+                remove_nulls(value);
+            }
+        }
+        serde_yaml::Value::Sequence(sequence) => sequence.iter_mut().for_each(remove_nulls),
+        _ => {}
+    }
+}
+
+/// 序列化为元信息 YAML, 去掉只在 metadata.json 中出现的字段和值为空的字段
+pub fn to_yaml(metadata: &MetaData) -> anyhow::Result<String> {
+    let mut value = serde_yaml::to_value(metadata)?;
+    if let Some(data) = value.get_mut("data").and_then(|data| data.as_mapping_mut()) {
+        for key in ["filesize", "page_count", "has_text_layer"] {
+            data.remove(key);
+        }
+    }
+    remove_nulls(&mut value);
+    Ok(serde_yaml::to_string(&value)?)
+}
+
+/// 找出文本中所有有效的 ISBN, 统一转换为带连字符的 ISBN-13, 按出现顺序去重
+pub fn find_isbns(text: &str) -> Vec<String> {
+    let regex = regex::Regex::new(r"(?:97[89][\s-]?)?\d(?:[\s-]?\d){8}[\s-]?[\dXx]").unwrap();
+    let mut isbns = Vec::new();
+    for candidate in regex.find_iter(text) {
+        if candidate.as_str().parse::<isbn::Isbn>().is_err() {
+            continue;
+        }
+        let isbn = hyphenate_isbn(candidate.as_str());
+        if !isbns.contains(&isbn) {
+            isbns.push(isbn);
+        }
+    }
+    isbns
+}
+
+/// 作者字段中常见的分隔符: 逗号、分号、顿号和 "and"
+fn split_authors(author: &str) -> Vec<String> {
+    author
+        .split([',', ';', '，', '；', '、', '&'])
+        .flat_map(|part| part.split(" and "))
+        .map(str::trim)
+        .filter(|author| !author.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// PDF 日期形如 `D:20150312093000+08'00'`, 取出年份
+fn pdf_date_year(date: &str) -> Option<String> {
+    let digits = date.trim().trim_start_matches("D:");
+    let year = digits.get(..4)?;
+    (year.chars().all(|c| c.is_ascii_digit()) && year > "1900").then(|| year.to_string())
+}
+
+/// 根据文档信息和前几页的文字生成书籍元信息草稿, 无法确定的字段留空
+pub fn book_draft(md5: &str, site_url: &str, info: &DocumentInfo, text: &str) -> MetaData {
+    let title = info
+        .title
+        .as_deref()
+        .map(str::trim)
+        .unwrap_or_default()
+        .to_string();
+    MetaData {
+        id: md5.to_string(),
+        url: file_url(site_url, md5, "pdf"),
+        type_: Type::Book,
+        data: Data::Book(Book {
+            title,
+            authors: info
+                .author
+                .as_deref()
+                .map(split_authors)
+                .unwrap_or_default(),
+            translators: None,
+            edition: None,
+            publish_year: info.creation_date.as_deref().and_then(pdf_date_year),
+            publisher: None,
+            isbn: find_isbns(text),
+            filetype: "pdf".to_string(),
+            filesize: None,
+            page_count: None,
+            has_text_layer: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_find_isbns() {
+        let text = "ISBN 978-7-111-40772-0\n定价: 45.00元 ISBN：7-111-40772-X 9787111407720 电话 010-88379833";
+        assert_eq!(find_isbns(text), ["978-7-111-40772-0"]);
+        assert_eq!(find_isbns("ISBN 0-306-40615-2"), ["978-0-306-40615-7"]);
+    }
+
+    #[test]
+    fn test_book_draft() {
+        let info = DocumentInfo {
+            title: Some(" 数据结构 ".to_string()),
+            author: Some("严蔚敏, 吴伟民".to_string()),
+            creation_date: Some("D:20150312093000+08'00'".to_string()),
+        };
+        let md5 = "0123456789abcdef0123456789abcdef";
+        let draft = book_draft(md5, "https://byrdocs.org/", &info, "ISBN 978-7-111-40772-0");
+        let yaml = to_yaml(&draft).unwrap();
+        assert_eq!(
+            yaml,
+            "id: 0123456789abcdef0123456789abcdef\nurl: https://byrdocs.org/files/0123456789abcdef0123456789abcdef.pdf\ntype: book\ndata:\n  title: 数据结构\n  authors:\n  - 严蔚敏\n  - 吴伟民\n  publish_year: '2015'\n  isbn:\n  - 978-7-111-40772-0\n  filetype: pdf\n"
+        );
+        let parsed: MetaData = serde_yaml::from_str(&yaml).unwrap();
+        assert!(crate::validate::check_data(&parsed.data).is_empty());
+    }
+
+    #[test]
+    fn test_draft() {
+        assert_eq!(detect_filetype(b"%PDF-1.7\n"), Some("pdf"));
+        assert_eq!(detect_filetype(b"PK\x03\x04"), Some("zip"));
+        assert_eq!(detect_filetype(b"Rar!"), None);
+
+        let md5 = "0123456789abcdef0123456789abcdef";
+        let fields = Fields {
+            courses: vec!["数据结构".to_string()],
+            course_type: Some("本科".to_string()),
+            start: Some("2023".to_string()),
+            stage: Some("期末".to_string()),
+            content: vec!["原题".to_string()],
+            ..Default::default()
+        };
+        let test = draft(Type::Test, md5, "zip", "https://byrdocs.org", fields);
+        assert_eq!(
+            to_yaml(&test).unwrap(),
+            "id: 0123456789abcdef0123456789abcdef\nurl: https://byrdocs.org/files/0123456789abcdef0123456789abcdef.zip\ntype: test\ndata:\n  course:\n    type: 本科\n    name: 数据结构\n  time:\n    start: '2023'\n    end: '2023'\n    stage: 期末\n  filetype: zip\n  content:\n  - 原题\n"
+        );
+        assert!(crate::validate::check_data(&test.data).is_empty());
+
+        let doc = draft(
+            Type::Doc,
+            md5,
+            "pdf",
+            "https://byrdocs.org",
+            Fields::default(),
+        );
+        let rules = crate::validate::check_data(&doc.data)
+            .into_iter()
+            .map(|finding| finding.rule)
+            .collect::<Vec<_>>();
+        assert_eq!(rules, ["empty-course", "empty-content"]);
+    }
+
+    #[test]
+    fn test_missing_metadata() {
+        let file = |file_name: &str, status| TempFiles {
+            id: 1,
+            created_at: "2024-09-01T08:00:00Z".to_string(),
+            file_name: file_name.to_string(),
+            file_size: None,
+            uploader: "alice".to_string(),
+            upload_time: None,
+            status,
+            error_message: None,
+        };
+        let files = [
+            file("0123456789abcdef0123456789abcdef.pdf", Status::Uploaded),
+            file("11111111111111111111111111111111.zip", Status::Uploaded),
+            file("22222222222222222222222222222222.pdf", Status::Pending),
+            file("33333333333333333333333333333333.rar", Status::Uploaded),
+        ];
+        let existing = HashSet::from(["11111111111111111111111111111111".to_string()]);
+        let missing = missing_metadata(&files, &existing);
+        assert_eq!(missing.len(), 1);
+        let (file, md5, filetype) = missing[0];
+        assert_eq!(md5, "0123456789abcdef0123456789abcdef");
+        assert_eq!(filetype, "pdf");
+        assert_eq!(
+            upload_comment(file),
+            "# 上传者: alice, 上传时间: 2024-09-01T08:00:00Z\n"
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{export::flatten, metadata::*};
+
+pub const STATS_JSON_NAME: &str = "stats.json";
+pub const STATS_MARKDOWN_NAME: &str = "stats.md";
+
+/// 文件数和已知的总大小
+#[derive(Serialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Count {
+    pub count: usize,
+    /// 单位为字节, 只统计有 filesize 的文件
+    pub size: i64,
+}
+
+impl Count {
+    fn add(&mut self, filesize: Option<i64>) {
+        self.count += 1;
+        self.size += filesize.unwrap_or_default();
+    }
+}
+
+/// 元信息的统计, 各分组按名称排序
+#[derive(Serialize, Debug, Default, PartialEq)]
+pub struct Stats {
+    pub total: Count,
+    /// 没有 filesize 的文件数, 未配置 R2 时等于总数
+    pub unknown_size: usize,
+    pub by_type: BTreeMap<&'static str, Count>,
+    /// 试卷的课程和资料的每门课程
+    pub by_course: BTreeMap<String, usize>,
+    pub by_college: BTreeMap<String, usize>,
+    /// 试卷的学年, 如 `2023-2024`
+    pub by_year: BTreeMap<String, usize>,
+    pub by_stage: BTreeMap<String, usize>,
+    /// 试卷和资料的每项 content
+    pub by_content: BTreeMap<String, usize>,
+}
+
+pub fn collect_stats(records: &[MetaData]) -> Stats {
+    let mut stats = Stats::default();
+    for metadata in records {
+        let flat = flatten(metadata);
+        stats.total.add(flat.filesize);
+        if flat.filesize.is_none() {
+            stats.unknown_size += 1;
+        }
+        stats
+            .by_type
+            .entry(flat.type_)
+            .or_default()
+            .add(flat.filesize);
+        for course in &flat.courses {
+            *stats.by_course.entry(course.name.clone()).or_default() += 1;
+        }
+        for college in flat.colleges {
+            *stats.by_college.entry(college.clone()).or_default() += 1;
+        }
+        if let Some(time) = flat.time {
+            let year = format!("{}-{}", time.start, time.end);
+            *stats.by_year.entry(year).or_default() += 1;
+            if let Some(stage) = &time.stage {
+                *stats.by_stage.entry(stage.clone()).or_default() += 1;
+            }
+        }
+        for content in flat.content {
+            *stats.by_content.entry(content.clone()).or_default() += 1;
+        }
+    }
+    stats
+}
+
+/// 以 1024 为进制的可读大小
+pub fn format_size(size: i64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", size)
+    } else {
+        format!("{:.1} {}", value, UNITS[unit])
+    }
+}
+
+fn type_name(type_: &str) -> &str {
+    match type_ {
+        "test" => "试卷",
+        "book" => "书籍",
+        "doc" => "资料",
+        other => other,
+    }
+}
+
+/// 按数量从多到少排列, 数量相同时按名称排列
+fn count_table(markdown: &mut String, name: &str, counts: &BTreeMap<String, usize>) {
+    if counts.is_empty() {
+        return;
+    }
+    let mut counts = counts.iter().collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    markdown.push_str(&format!(
+        "\n## {}\n\n| {} | 数量 |\n| --- | ---: |\n",
+        name, name
+    ));
+    for (name, count) in counts {
+        markdown.push_str(&format!("| {} | {} |\n", name.replace('|', "\\|"), count));
+    }
+}
+
+pub fn to_markdown(stats: &Stats) -> String {
+    let mut markdown = format!(
+        "# 统计\n\n共 {} 个文件, 总大小 {}",
+        stats.total.count,
+        format_size(stats.total.size)
+    );
+    if stats.unknown_size != 0 {
+        markdown.push_str(&format!(" (其中 {} 个文件大小未知)", stats.unknown_size));
+    }
+    markdown.push_str("\n\n## 类型\n\n| 类型 | 数量 | 大小 |\n| --- | ---: | ---: |\n");
+    for (type_, count) in &stats.by_type {
+        markdown.push_str(&format!(
+            "| {} | {} | {} |\n",
+            type_name(type_),
+            count.count,
+            format_size(count.size)
+        ));
+    }
+    // 学年按时间倒序, 其他分组按数量排列
+    if !stats.by_year.is_empty() {
+        markdown.push_str("\n## 学年\n\n| 学年 | 数量 |\n| --- | ---: |\n");
+        for (year, count) in stats.by_year.iter().rev() {
+            markdown.push_str(&format!("| {} | {} |\n", year, count));
+        }
+    }
+    count_table(&mut markdown, "阶段", &stats.by_stage);
+    count_table(&mut markdown, "内容", &stats.by_content);
+    count_table(&mut markdown, "学院", &stats.by_college);
+    count_table(&mut markdown, "课程", &stats.by_course);
+    markdown
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::fixtures::records;
+
+    #[test]
+    fn test_collect_stats() {
+        let stats = collect_stats(&records());
+        assert_eq!(
+            stats.total,
+            Count {
+                count: 5,
+                size: 3 * 1024 * 1024 + 1024
+            }
+        );
+        assert_eq!(stats.unknown_size, 3);
+        assert_eq!(
+            stats.by_type["test"],
+            Count {
+                count: 2,
+                size: 1024
+            }
+        );
+        assert_eq!(stats.by_course["数据结构"], 2);
+        assert_eq!(stats.by_course["高等数学A"], 2);
+        assert_eq!(stats.by_college["计算机学院"], 1);
+        assert_eq!(
+            stats.by_year.keys().collect::<Vec<_>>(),
+            ["2022-2023", "2023-2024"]
+        );
+        assert_eq!(stats.by_stage.len(), 2);
+        assert_eq!(stats.by_content["原题"], 2);
+        assert_eq!(stats.by_content["知识点"], 1);
+    }
+
+    #[test]
+    fn test_to_markdown() {
+        let markdown = to_markdown(&collect_stats(&records()));
+        assert!(
+            markdown.starts_with("# 统计\n\n共 5 个文件, 总大小 3.0 MiB (其中 3 个文件大小未知)\n")
+        );
+        assert!(markdown.contains("| 书籍 | 2 | 3.0 MiB |\n"));
+        assert!(
+            markdown.contains(
+                "| 学年 | 数量 |\n| --- | ---: |\n| 2023-2024 | 1 |\n| 2022-2023 | 1 |\n"
+            )
+        );
+        assert!(markdown.contains("| 数据结构 | 2 |\n| 高等数学A | 2 |\n"));
+        assert_eq!(format_size(1000), "1000 B");
+    }
+}
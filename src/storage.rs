@@ -0,0 +1,108 @@
+use rusoto_core::HttpClient;
+use rusoto_s3::{Object, S3, S3Client};
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncReadExt, BufReader};
+
+use crate::metadata::{Data, MetaData};
+
+pub async fn get_s3_client(
+    r2_endpoint: String,
+    r2_endpoint_region: String,
+    access_key_id: String,
+    secret_access_key: String,
+) -> anyhow::Result<S3Client> {
+    let http_client = HttpClient::new()?;
+    let credentials =
+        rusoto_core::credential::StaticProvider::new_minimal(access_key_id, secret_access_key);
+    let region = rusoto_core::Region::Custom {
+        name: r2_endpoint_region,
+        endpoint: r2_endpoint,
+    };
+    let s3_client = rusoto_s3::S3Client::new_with(http_client, credentials, region);
+    Ok(s3_client)
+}
+
+pub async fn list_all_objects(client: &rusoto_s3::S3Client, bucket: &str) -> Vec<Object> {
+    let mut continuation_token: Option<String> = None;
+    let mut s3_file_list = Vec::new();
+
+    loop {
+        let request = rusoto_s3::ListObjectsV2Request {
+            bucket: bucket.to_string(),
+            continuation_token: continuation_token.clone(),
+            ..Default::default()
+        };
+
+        match client.list_objects_v2(request).await {
+            Ok(output) => {
+                if let Some(contents) = output.contents {
+                    s3_file_list.extend(contents);
+                }
+
+                if let Some(next_continuation_token) = output.next_continuation_token {
+                    continuation_token = Some(next_continuation_token);
+                } else {
+                    break;
+                }
+            }
+            Err(rusoto_core::RusotoError::Unknown(resp)) => {
+                eprintln!("Error: {}", resp.status);
+                break;
+            }
+            Err(e) => {
+                eprintln!("Error: {:?}", e);
+                break;
+            }
+        }
+    }
+    s3_file_list
+}
+
+/// 从 R2 的数据桶下载上次运行保存的 JSON 文件, 不存在时使用默认值
+pub async fn download_data_json<T: DeserializeOwned + Default>(
+    s3_client: &S3Client,
+    bucket: &str,
+    key: &str,
+) -> anyhow::Result<T> {
+    let request = rusoto_s3::GetObjectRequest {
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        ..Default::default()
+    };
+    let body = match s3_client.get_object(request).await {
+        Ok(result) => result.body.unwrap(),
+        Err(rusoto_core::RusotoError::Service(rusoto_s3::GetObjectError::NoSuchKey(_))) => {
+            println!("{} not found, starting from an empty one", key);
+            return Ok(T::default());
+        }
+        Err(rusoto_core::RusotoError::Unknown(resp)) if resp.status.as_u16() == 404 => {
+            println!("{} not found, starting from an empty one", key);
+            return Ok(T::default());
+        }
+        Err(e) => return Err(e.into()),
+    };
+    let mut reader = BufReader::new(body.into_async_read());
+    let mut buf = Vec::new();
+    reader.read_to_end(&mut buf).await?;
+    Ok(serde_json::from_slice(&buf)?)
+}
+
+/// 文件桶中的文件名为 `{md5}.pdf` 或 `{md5}.zip`
+pub fn get_file_size(id: &str, s3_obj: &[Object]) -> Option<i64> {
+    s3_obj
+        .iter()
+        .find(|file| {
+            file.key == Some(format!("{}.pdf", id)) || file.key == Some(format!("{}.zip", id))
+        })
+        .and_then(|file| file.size)
+}
+
+/// 按文件桶中的大小设置 `filesize`, 与 metadata.json 中的一致
+pub fn fill_file_size(metadata: &mut MetaData, s3_obj: &[Object]) {
+    let filesize = get_file_size(&metadata.id, s3_obj);
+    match metadata.data {
+        Data::Book(ref mut book) => book.filesize = filesize,
+        Data::Doc(ref mut doc) => doc.filesize = filesize,
+        Data::Test(ref mut test) => test.filesize = filesize,
+    }
+}
@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use crate::{metadata::*, rules::Finding};
+
+pub fn check_data(data: &Data) -> Vec<Finding> {
+    match data {
+        Data::Test(test) => check_test(test),
+        Data::Book(book) => check_book(book),
+        Data::Doc(doc) => check_doc(doc),
+    }
+}
+
+pub fn check_book(book: &Book) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if book.authors.is_empty() {
+        findings.push(Finding::new("book-authors", "应当至少有一个作者"));
+    }
+    let year_regex = regex::Regex::new(r"^\d{4}$").unwrap();
+    if let Some(year) = &book.publish_year
+        && !year_regex.is_match(year)
+    {
+        findings.push(Finding::new("publish-year", "请检查出版年份"));
+    }
+    book.isbn.iter().for_each(|isbn| {
+        if let Err(e) = isbn.parse::<isbn::Isbn13>() {
+            findings
+                .push(Finding::new("isbn-format", format!("请检查isbn格式: {}", e)).with_key(isbn));
+        }
+    });
+    if book.filetype != "pdf" {
+        findings.push(Finding::new("filetype", "请检查filetype，只能为pdf"));
+    }
+    findings
+}
+
+/// 记录已出现过的isbn, 后出现的书籍会被报告为重复
+pub fn check_duplicate_isbn(
+    book: &Book,
+    md5: &str,
+    seen: &mut HashMap<isbn::Isbn13, String>,
+) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for isbn in &book.isbn {
+        let Ok(isbn) = isbn.parse::<isbn::Isbn13>() else {
+            continue;
+        };
+        if let Some(existing_md5) = seen.get(&isbn) {
+            if existing_md5 == md5 {
+                // 同一本书中重复的isbn由 duplicate-value 报告
+                continue;
+            }
+            findings.push(
+                Finding::new(
+                    "duplicate-isbn",
+                    format!("重复的isbn. md5: {} {}", md5, existing_md5),
+                )
+                .with_key(isbn.to_string()),
+            );
+        } else {
+            seen.insert(isbn, md5.to_string());
+        }
+    }
+    findings
+}
+
+pub fn check_test(test: &Test) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if let Some(type_) = &test.course.type_
+        && !["本科", "研究生"].contains(&type_.as_str())
+    {
+        findings.push(Finding::new(
+            "course-type",
+            "请检查course type，只能为\"本科\"或\"研究生\"",
+        ));
+    }
+    if let Some(stage) = &test.time.stage
+        && !["期中", "期末"].contains(&stage.as_str())
+    {
+        findings.push(Finding::new(
+            "stage",
+            "请检查stage，只能为\"期中\"或\"期末\"",
+        ));
+    }
+    if let Some(semester) = &test.time.semester
+        && !["First", "Second"].contains(&semester.as_str())
+    {
+        findings.push(Finding::new(
+            "semester",
+            "请检查semester，只能为\"First\"或\"Second\"",
+        ));
+    }
+    for content in &test.content {
+        match content.as_str() {
+            "原题" => (),
+            "答案" => (),
+            _ => findings.push(Finding::new(
+                "content",
+                "错误的content，content只能为\"原题\"或\"答案\"",
+            )),
+        }
+    }
+    if let Some(colleges) = &test.college
+        && colleges.contains(&"".to_string())
+    {
+        findings.push(Finding::new("empty-college", "college不能存在空字符串"));
+    }
+    if let (Ok(start), Ok(end)) = (test.time.start.parse::<u32>(), test.time.end.parse::<u32>()) {
+        if !(start == end || start + 1 == end) {
+            findings.push(Finding::new("time", "请检查时间"));
+        }
+    } else {
+        findings.push(Finding::new("time", "时间格式不正确"));
+    }
+    if test.filetype != "pdf" && test.filetype != "zip" {
+        findings.push(Finding::new("filetype", "请检查filetype，只能为pdf或zip"));
+    }
+    if test.content.is_empty() {
+        findings.push(Finding::new("empty-content", "content不能为空"));
+    }
+    findings
+}
+
+pub fn check_doc(doc: &Doc) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    if doc.course.is_empty() {
+        findings.push(Finding::new("empty-course", "course不能为空"));
+    }
+    for course in &doc.course {
+        if let Some(type_) = &course.type_
+            && !["本科", "研究生"].contains(&type_.as_str())
+        {
+            findings.push(Finding::new(
+                "course-type",
+                "请检查course type，只能为\"本科\"或\"研究生\"",
+            ));
+        }
+    }
+    if doc.content.is_empty() {
+        findings.push(Finding::new("empty-content", "content不能为空"));
+    }
+    for content in &doc.content {
+        match content.as_str() {
+            "思维导图" => (),
+            "题库" => (),
+            "答案" => (),
+            "知识点" => (),
+            "课件" => (),
+            _ => findings.push(Finding::new(
+                "content",
+                r#"错误的content，content只能为"思维导图"、"题库"、"答案"、"知识点"或"课件""#,
+            )),
+        }
+    }
+    if doc.filetype != "pdf" && doc.filetype != "zip" {
+        findings.push(Finding::new("filetype", "请检查filetype，只能为pdf或zip"));
+    }
+    findings
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_isbn() {
+        let isbn = "978-7-111-40772-1";
+        assert_eq!(
+            isbn.parse::<isbn::Isbn13>(),
+            Err(isbn::IsbnError::InvalidDigit)
+        );
+        let isbn = "978-7-111-40772-0";
+        assert_eq!(
+            isbn.parse::<isbn::Isbn13>(),
+            Ok(isbn::Isbn13::new([9, 7, 8, 7, 1, 1, 1, 4, 0, 7, 7, 2, 0]).unwrap())
+        );
+        let isbn = "9787111407720";
+        assert_eq!(
+            isbn.parse::<isbn::Isbn13>(),
+            Ok(isbn::Isbn13::new([9, 7, 8, 7, 1, 1, 1, 4, 0, 7, 7, 2, 0]).unwrap())
+        );
+        assert_eq!(
+            "978-7-111-40772-0",
+            isbn.parse::<isbn::Isbn13>()
+                .unwrap()
+                .hyphenate()
+                .unwrap()
+                .as_str()
+        );
+    }
+
+    #[test]
+    fn test_duplicate_isbn() {
+        let book: Book = serde_yaml::from_str(
+            "title: t\nauthors: [a]\nisbn: [978-7-111-40772-0]\nfiletype: pdf\n",
+        )
+        .unwrap();
+        let mut seen = HashMap::new();
+        assert!(check_duplicate_isbn(&book, "a", &mut seen).is_empty());
+        let findings = check_duplicate_isbn(&book, "b", &mut seen);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].rule, "duplicate-isbn");
+        assert!(check_book(&book).is_empty());
+    }
+}